@@ -1,9 +1,79 @@
 
-pub(crate) const RX_RING_SIZE:usize = 8;
-pub(crate) const TX_RING_SIZE:usize = 8;
-pub(crate) const RXTX_SINGLE_RING_BLOCK_SIZE:usize = 16384;
+/* Number of descriptors in the rings, can be changed with `ethtool -G` */
+pub(crate) const E1000_DEFAULT_TXD:usize = 256;
+pub(crate) const E1000_MAX_TXD:usize = 4096;
+pub(crate) const E1000_MIN_TXD:usize = 80;
+pub(crate) const E1000_DEFAULT_RXD:usize = 256;
+pub(crate) const E1000_MAX_RXD:usize = 4096;
+pub(crate) const E1000_MIN_RXD:usize = 80;
+/* The descriptor ring length must be a multiple of 128 bytes, which is 8 descriptors */
+pub(crate) const E1000_REQ_DESCRIPTOR_MULTIPLE:usize = 8;
 
-pub(crate) const MAC_HWADDR: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+/* Supported Rx Buffer Sizes */
+pub(crate) const E1000_RXBUFFER_256:usize = 256;
+pub(crate) const E1000_RXBUFFER_512:usize = 512;
+pub(crate) const E1000_RXBUFFER_1024:usize = 1024;
+pub(crate) const E1000_RXBUFFER_2048:usize = 2048;
+pub(crate) const E1000_RXBUFFER_4096:usize = 4096;
+pub(crate) const E1000_RXBUFFER_8192:usize = 8192;
+pub(crate) const E1000_RXBUFFER_16384:usize = 16384;
+/* The larger rx buffers are pages, their frames are put in a skb whose linear part only holds the Ethernet header */
+pub(crate) const E1000_RX_HDR_LEN:usize = 128;
+
+/* Frame sizes, the FCS and a VLAN tag are counted so that the buffer always holds a whole frame */
+pub(crate) const MAXIMUM_ETHERNET_VLAN_SIZE:usize = 1522;
+pub(crate) const MAX_JUMBO_FRAME_SIZE:usize = 0x3F00;	/* the largest frame of the 82540 */
+pub(crate) const ENET_HEADER_SIZE:usize = 14;
+pub(crate) const ETHERNET_FCS_SIZE:usize = 4;
+pub(crate) const VLAN_TAG_SIZE:usize = 4;
+
+/* Tx buffers larger than this are split into several descriptors */
+pub(crate) const E1000_MAX_TXD_PWR:usize = 12;
+pub(crate) const E1000_MAX_DATA_PER_TXD:usize = 1 << E1000_MAX_TXD_PWR;
+/* Header offsets in a context descriptor are 8-bit */
+pub(crate) const E1000_MAX_CSUM_OFFSET:usize = 255;
+/* The default value of the C macro MAX_SKB_FRAGS */
+pub(crate) const MAX_SKB_FRAGS:usize = 17;
+/* The queue is stopped when fewer than this are unused, enough for a packet made of the linear part and the most page frags */
+pub(crate) const E1000_TX_DESC_NEEDED:usize = MAX_SKB_FRAGS + 2;
+/* The stopped tx queue is woken up when this many descriptors are unused again */
+pub(crate) const E1000_TX_WAKE_THRESHOLD:usize = 32;
+
+/* Interval of the watchdog, same as the C version */
+pub(crate) const E1000_WATCHDOG_INTERVAL_MS:u32 = 2000;
+
+/* Interrupt moderation. The delay timers count in units of 1.024us and ITR in units of 256ns, all of them are 16 bits */
+pub(crate) const E1000_MAX_INT_DELAY:u32 = 0xFFFF;
+pub(crate) const E1000_MAX_ITR:u32 = 0xFFFF;
+/* The range of the interrupt throttle rate in interrupts per second, same as the C version */
+pub(crate) const E1000_MIN_INTS_PER_SEC:u32 = 100;
+pub(crate) const E1000_MAX_INTS_PER_SEC:u32 = 100000;
+/* Default settings, same as the C version. ITR is in interrupts per second */
+pub(crate) const E1000_DEFAULT_RDTR_USECS:u32 = 0;
+pub(crate) const E1000_DEFAULT_RADV_USECS:u32 = 8;
+pub(crate) const E1000_DEFAULT_TIDV_USECS:u32 = 8;
+pub(crate) const E1000_DEFAULT_TADV_USECS:u32 = 32;
+pub(crate) const E1000_DEFAULT_ITR:u32 = 20000;
+
+/* The coalescing parameters supported by `ethtool -C`, the C macros ETHTOOL_COALESCE_* */
+pub(crate) const ETHTOOL_COALESCE_RX_USECS:u32 = 1 << 0;
+pub(crate) const ETHTOOL_COALESCE_TX_USECS:u32 = 1 << 4;
+pub(crate) const ETHTOOL_COALESCE_USE_ADAPTIVE_RX:u32 = 1 << 9;
+pub(crate) const ETHTOOL_COALESCE_RX_USECS_HIGH:u32 = 1 << 17;
+pub(crate) const ETHTOOL_COALESCE_TX_USECS_HIGH:u32 = 1 << 19;
+
+/* Net device features, the C macros NETIF_F_* */
+pub(crate) const NETIF_F_SG:u64 = 1 << kernel::bindings::NETIF_F_SG_BIT;	/* Scatter/gather IO */
+pub(crate) const NETIF_F_HW_CSUM:u64 = 1 << kernel::bindings::NETIF_F_HW_CSUM_BIT;	/* Can checksum all the packets */
+pub(crate) const NETIF_F_RXCSUM:u64 = 1 << kernel::bindings::NETIF_F_RXCSUM_BIT;	/* Receive checksumming offload */
+pub(crate) const NETIF_F_TSO:u64 = 1 << kernel::bindings::NETIF_F_TSO_BIT;	/* TCPv4 segmentation */
+pub(crate) const NETIF_F_TSO6:u64 = 1 << kernel::bindings::NETIF_F_TSO6_BIT;	/* TCPv6 segmentation */
+pub(crate) const NETIF_F_HW_VLAN_CTAG_TX:u64 = 1 << kernel::bindings::NETIF_F_HW_VLAN_CTAG_TX_BIT;	/* Transmit VLAN CTAG HW acceleration */
+pub(crate) const NETIF_F_HW_VLAN_CTAG_RX:u64 = 1 << kernel::bindings::NETIF_F_HW_VLAN_CTAG_RX_BIT;	/* Receive VLAN CTAG HW acceleration */
+pub(crate) const NETIF_F_HW_VLAN_CTAG_FILTER:u64 = 1 << kernel::bindings::NETIF_F_HW_VLAN_CTAG_FILTER_BIT;	/* Receive filtering on VLAN CTAGs */
+/* The features supported by this driver, all of them are enabled by default */
+pub(crate) const E1000_FEATURES:u64 = NETIF_F_SG | NETIF_F_HW_CSUM | NETIF_F_RXCSUM | NETIF_F_TSO | NETIF_F_TSO6 |
+    NETIF_F_HW_VLAN_CTAG_TX | NETIF_F_HW_VLAN_CTAG_RX | NETIF_F_HW_VLAN_CTAG_FILTER;
 
 pub(crate) const E1000_VENDER_ID:u32 = 0x8086;
 pub(crate) const E1000_DEVICE_ID:u32 = 0x100E;
@@ -13,14 +83,19 @@ pub(crate) const E1000_DEVICE_ID:u32 = 0x100E;
 
 pub(crate) const E1000_CTRL:usize = 0x00000;	/* Device Control - RW */
 pub(crate) const E1000_STATUS:usize = 0x00008;	/* Device Status - RO */
+pub(crate) const E1000_EECD:usize = 0x00010;	/* EEPROM/Flash Control - RW */
+pub(crate) const E1000_EERD:usize = 0x00014;	/* EEPROM Read - RW */
 pub(crate) const E1000_IMC:usize = 0x000D8;	/* Interrupt Mask Clear - WO */
 pub(crate) const E1000_IMS:usize = 0x000D0;	/* Interrupt Mask Set - RW */
 pub(crate) const E1000_RCTL:usize = 0x00100;	/* RX Control - RW */
 pub(crate) const E1000_TCTL:usize = 0x00400;	/* TX Control - RW */
 pub(crate) const E1000_MANC:usize = 0x05820;	/* Management Control - RW */
 pub(crate) const E1000_ICR:usize = 0x000C0;	/* Interrupt Cause Read - R/clr */
+pub(crate) const E1000_ICS:usize = 0x000C8;	/* Interrupt Cause Set - WO */
+pub(crate) const E1000_ITR:usize = 0x000C4;	/* Interrupt Throttling Rate - RW */
 pub(crate) const E1000_RA:usize = 0x05400;	/* Receive Address - RW Array */
 pub(crate) const E1000_MTA:usize = 0x05200 ;	/* Multicast Table Array - RW Array */
+pub(crate) const E1000_VFTA:usize = 0x05600;	/* VLAN Filter Table Array - RW Array */
 
 pub(crate) const E1000_RDH:usize = 0x02810;	/* RX Descriptor Head - RW */
 pub(crate) const E1000_RDT:usize = 0x02818;	/* RX Descriptor Tail - RW */
@@ -34,8 +109,42 @@ pub(crate) const E1000_TDBAL:usize = 0x03800;	/* TX Descriptor Base Address Low
 pub(crate) const E1000_TDBAH:usize = 0x03804;	/* TX Descriptor Base Address High - RW */
 pub(crate) const E1000_TIPG:usize = 0x00410;	/* TX Inter-packet gap -RW */
 
+/* Statistics registers, all of them are cleared on read */
+pub(crate) const E1000_CRCERRS:usize = 0x04000;	/* CRC Error Count - R/clr */
+pub(crate) const E1000_ALGNERRC:usize = 0x04004;	/* Alignment Error Count - R/clr */
+pub(crate) const E1000_SYMERRS:usize = 0x04008;	/* Symbol Error Count - R/clr */
+pub(crate) const E1000_RXERRC:usize = 0x0400C;	/* Receive Error Count - R/clr */
+pub(crate) const E1000_MPC:usize = 0x04010;	/* Missed Packet Count - R/clr */
+pub(crate) const E1000_SCC:usize = 0x04014;	/* Single Collision Count - R/clr */
+pub(crate) const E1000_ECOL:usize = 0x04018;	/* Excessive Collision Count - R/clr */
+pub(crate) const E1000_MCC:usize = 0x0401C;	/* Multiple Collision Count - R/clr */
+pub(crate) const E1000_LATECOL:usize = 0x04020;	/* Late Collision Count - R/clr */
+pub(crate) const E1000_COLC:usize = 0x04028;	/* Collision Count - R/clr */
+pub(crate) const E1000_DC:usize = 0x04030;	/* Defer Count - R/clr */
+pub(crate) const E1000_TNCRS:usize = 0x04034;	/* TX-No CRS - R/clr */
+pub(crate) const E1000_CEXTERR:usize = 0x0403C;	/* Carrier Extension Error Count - R/clr */
+pub(crate) const E1000_RLEC:usize = 0x04040;	/* Receive Length Error Count - R/clr */
+pub(crate) const E1000_GPRC:usize = 0x04074;	/* Good Packets RX Count - R/clr */
+pub(crate) const E1000_BPRC:usize = 0x04078;	/* Broadcast Packets RX Count - R/clr */
+pub(crate) const E1000_MPRC:usize = 0x0407C;	/* Multicast Packets RX Count - R/clr */
+pub(crate) const E1000_GPTC:usize = 0x04080;	/* Good Packets TX Count - R/clr */
+pub(crate) const E1000_GORCL:usize = 0x04088;	/* Good Octets RX Count Low - R/clr */
+pub(crate) const E1000_GORCH:usize = 0x0408C;	/* Good Octets RX Count High - R/clr */
+pub(crate) const E1000_GOTCL:usize = 0x04090;	/* Good Octets TX Count Low - R/clr */
+pub(crate) const E1000_GOTCH:usize = 0x04094;	/* Good Octets TX Count High - R/clr */
+pub(crate) const E1000_RNBC:usize = 0x040A0;	/* RX No Buffers Count - R/clr */
+pub(crate) const E1000_RUC:usize = 0x040A4;	/* RX Undersize Count - R/clr */
+pub(crate) const E1000_ROC:usize = 0x040AC;	/* RX Oversize Count - R/clr */
+pub(crate) const E1000_TPR:usize = 0x040D0;	/* Total Packets RX - R/clr */
+pub(crate) const E1000_TPT:usize = 0x040D4;	/* Total Packets TX - R/clr */
+pub(crate) const E1000_MPTC:usize = 0x040F0;	/* Multicast Packets TX Count - R/clr */
+pub(crate) const E1000_BPTC:usize = 0x040F4;	/* Broadcast Packets TX Count - R/clr */
+
+pub(crate) const E1000_RXCSUM:usize = 0x05000;	/* RX Checksum Control - RW */
 pub(crate) const E1000_RDTR:usize = 0x02820;	/* RX Delay Timer - RW */
 pub(crate) const E1000_RADV:usize = 0x0282C;	/* RX Interrupt Absolute Delay Timer - RW */
+pub(crate) const E1000_TIDV:usize = 0x03820;	/* TX Interrupt Delay Value - RW */
+pub(crate) const E1000_TADV:usize = 0x0382C;	/* TX Interrupt Absolute Delay Val - RW */
 
 // pub(crate) const E1000_:usize = ;	/*  */
 // pub(crate) const E1000_:usize = ;	/*  */
@@ -62,15 +171,54 @@ pub(crate) const E1000_COLD_SHIFT:u32 = 12;
 
 /* Receive Control */
 pub(crate) const E1000_RCTL_EN:u32 = 0x00000002;	/* enable */
+pub(crate) const E1000_RCTL_UPE:u32 = 0x00000008;	/* unicast promiscuous enable */
+pub(crate) const E1000_RCTL_MPE:u32 = 0x00000010;	/* multicast promiscuous enab */
+pub(crate) const E1000_RCTL_MO_SHIFT:u32 = 12;	/* multicast offset shift */
 pub(crate) const E1000_RCTL_BAM:u32 = 0x00008000;	/* broadcast enable */
+pub(crate) const E1000_RCTL_VFE:u32 = 0x00040000;	/* vlan filter enable */
+pub(crate) const E1000_RCTL_CFIEN:u32 = 0x00080000;	/* canonical form enable */
+pub(crate) const E1000_RCTL_LPE:u32 = 0x00000020;	/* long packet enable */
 pub(crate) const E1000_RCTL_SZ_2048:u32 = 0x00000000;	/* rx buffer size 2048 */
+pub(crate) const E1000_RCTL_SZ_1024:u32 = 0x00010000;	/* rx buffer size 1024 */
+pub(crate) const E1000_RCTL_SZ_512:u32 = 0x00020000;	/* rx buffer size 512 */
+pub(crate) const E1000_RCTL_SZ_256:u32 = 0x00030000;	/* rx buffer size 256 */
+/* these buffer sizes are valid if E1000_RCTL_BSEX is 1 */
+pub(crate) const E1000_RCTL_SZ_16384:u32 = 0x00010000;	/* rx buffer size 16384 */
+pub(crate) const E1000_RCTL_SZ_8192:u32 = 0x00020000;	/* rx buffer size 8192 */
+pub(crate) const E1000_RCTL_SZ_4096:u32 = 0x00030000;	/* rx buffer size 4096 */
+pub(crate) const E1000_RCTL_BSEX:u32 = 0x02000000;	/* Buffer size extension */
 pub(crate) const E1000_RCTL_SECRC:u32 = 0x04000000;	/* Strip Ethernet CRC */
 
 // pub(crate) const E1000_:u32 = ;	/*  */
 // pub(crate) const E1000_:u32 = ;	/*  */
 
 
+/* VLAN Filter Table, 4096 bits for the 12-bit VLAN IDs */
+pub(crate) const E1000_VLAN_FILTER_TBL_SIZE:usize = 128;
+pub(crate) const VLAN_N_VID:u16 = 4096;
+
+/* Receive Checksum Control */
+pub(crate) const E1000_RXCSUM_PCSS_MASK:u32 = 0x000000FF;	/* Packet Checksum Start */
+pub(crate) const E1000_RXCSUM_IPOFL:u32 = 0x00000100;	/* IPv4 checksum offload */
+pub(crate) const E1000_RXCSUM_TUOFL:u32 = 0x00000200;	/* TCP / UDP checksum offload */
+
+/* Device Status */
+pub(crate) const E1000_STATUS_FD:u32 = 0x00000001;	/* Full duplex.0=half,1=full */
+pub(crate) const E1000_STATUS_LU:u32 = 0x00000002;	/* Link up.0=no,1=link */
+pub(crate) const E1000_STATUS_TXOFF:u32 = 0x00000010;	/* transmission paused */
+pub(crate) const E1000_STATUS_SPEED_100:u32 = 0x00000040;	/* Speed 100Mb/s */
+pub(crate) const E1000_STATUS_SPEED_1000:u32 = 0x00000080;	/* Speed 1000Mb/s */
+
+/* Receive Address, RA[0] holds the station address and the rest are for the secondary ones */
+pub(crate) const E1000_RAR_ENTRIES:usize = 15;
+/* Multicast Table Array, hashed with bits 47:36 of the address (MO = 00) */
+pub(crate) const E1000_NUM_MTA_REGISTERS:usize = 128;
+pub(crate) const E1000_MC_FILTER_TYPE:u32 = 0;
+
+pub(crate) const E1000_RAH_AV:u32 = 0x80000000;	/* Receive descriptor valid */
+
 pub(crate) const E1000_CTRL_RST:u32 = 0x04000000;	/* Global reset */
+pub(crate) const E1000_CTRL_VME:u32 = 0x40000000;	/* IEEE VLAN mode enable */
 pub(crate) const E1000_MANC_ARP_EN:u32 = 0x00002000;	/* Enable ARP Request Filtering */
 
 
@@ -83,6 +231,28 @@ pub(crate) const E1000_MANC_ARP_EN:u32 = 0x00002000;	/* Enable ARP Request Filte
 
 
 
+/* EEPROM/Flash Control */
+pub(crate) const E1000_EECD_SK:u32 = 0x00000001;	/* EEPROM Clock */
+pub(crate) const E1000_EECD_CS:u32 = 0x00000002;	/* EEPROM Chip Select */
+pub(crate) const E1000_EECD_DI:u32 = 0x00000004;	/* EEPROM Data In */
+pub(crate) const E1000_EECD_DO:u32 = 0x00000008;	/* EEPROM Data Out */
+pub(crate) const E1000_EECD_REQ:u32 = 0x00000040;	/* EEPROM Access Request */
+pub(crate) const E1000_EECD_GNT:u32 = 0x00000080;	/* EEPROM Access Grant */
+pub(crate) const E1000_EECD_SIZE:u32 = 0x00000200;	/* EEPROM Size (0=64 word 1=256 word) */
+
+/* EEPROM Read */
+pub(crate) const E1000_EEPROM_RW_REG_START:u32 = 1;	/* First bit for telling part to start operation */
+pub(crate) const E1000_EEPROM_RW_REG_DONE:u32 = 0x10;	/* Offset to READ/WRITE done bit */
+pub(crate) const E1000_EEPROM_RW_ADDR_SHIFT:u32 = 8;	/* Shift to the address bits */
+pub(crate) const E1000_EEPROM_RW_REG_DATA:u32 = 16;	/* Offset to data in EEPROM read/write registers */
+
+/* EEPROM Commands - Microwire */
+pub(crate) const EEPROM_READ_OPCODE_MICROWIRE:u16 = 0x6;	/* EEPROM read opcode */
+
+/* EEPROM Word Offsets */
+pub(crate) const EEPROM_CHECKSUM_REG:u16 = 0x003F;
+pub(crate) const EEPROM_SUM:u16 = 0xBABA;	/* For checksumming, the sum of all words in the EEPROM should equal 0xBABA. */
+
 /* Default values for the transmit IPG register */
 pub(crate) const DEFAULT_82543_TIPG_IPGT_COPPER:u32 = 8;
 pub(crate) const DEFAULT_82543_TIPG_IPGR1:u32 = 8;
@@ -96,10 +266,41 @@ pub(crate) const E1000_TIPG_IPGR2_SHIFT:u32 = 20;
 pub(crate) const E1000_TXD_STAT_DD:u32 = 0x00000001;	/* Descriptor Done */
 pub(crate) const E1000_TXD_CMD_RS:u32 = 0x08000000;	    /* Report Status */
 pub(crate) const E1000_TXD_CMD_EOP:u32 = 0x01000000;	/* End of Packet */
+pub(crate) const E1000_TXD_CMD_DEXT:u32 = 0x20000000;	/* Descriptor extension (0 = legacy) */
+pub(crate) const E1000_TXD_CMD_TCP:u32 = 0x01000000;	/* TCP packet */
+pub(crate) const E1000_TXD_CMD_IP:u32 = 0x02000000;	/* IP packet */
+pub(crate) const E1000_TXD_CMD_TSE:u32 = 0x04000000;	/* TCP Seg enable */
+pub(crate) const E1000_TXD_CMD_VLE:u32 = 0x40000000;	/* Add VLAN tag */
+pub(crate) const E1000_TXD_CMD_IDE:u32 = 0x80000000;	/* Enable Tidv register */
+pub(crate) const E1000_TXD_DTYP_D:u32 = 0x00100000;	/* Data Descriptor */
+pub(crate) const E1000_TXD_DTYP_C:u32 = 0x00000000;	/* Context Descriptor */
+pub(crate) const E1000_TXD_POPTS_IXSM:u32 = 0x01;	/* Insert IP checksum */
+pub(crate) const E1000_TXD_POPTS_TXSM:u32 = 0x02;	/* Insert TCP/UDP checksum */
+
+/* Flags of a tx packet, passed from `start_xmit()` to `e1000_tx_map()` */
+pub(crate) const E1000_TX_FLAGS_CSUM:u32 = 0x00000001;
+pub(crate) const E1000_TX_FLAGS_VLAN:u32 = 0x00000002;
+pub(crate) const E1000_TX_FLAGS_TSO:u32 = 0x00000004;
+pub(crate) const E1000_TX_FLAGS_IPV4:u32 = 0x00000008;
+pub(crate) const E1000_TX_FLAGS_VLAN_MASK:u32 = 0xffff0000;	/* the tag to be inserted */
+pub(crate) const E1000_TX_FLAGS_VLAN_SHIFT:u32 = 16;
 
 
 /* Receive Descriptor bit definitions */
 pub(crate) const E1000_RXD_STAT_DD:u32 = 0x01;	/* Descriptor Done */
+pub(crate) const E1000_RXD_STAT_EOP:u32 = 0x02;	/* End of Packet */
+pub(crate) const E1000_RXD_STAT_IXSM:u32 = 0x04;	/* Ignore checksum */
+pub(crate) const E1000_RXD_STAT_VP:u32 = 0x08;	/* IEEE VLAN Packet */
+pub(crate) const E1000_RXD_STAT_TCPCS:u32 = 0x20;	/* TCP xsum calculated */
+pub(crate) const E1000_RXD_STAT_IPCS:u32 = 0x40;	/* IP xsum calculated */
+pub(crate) const E1000_RXD_ERR_CE:u32 = 0x01;	/* CRC Error */
+pub(crate) const E1000_RXD_ERR_SE:u32 = 0x02;	/* Symbol Error */
+pub(crate) const E1000_RXD_ERR_SEQ:u32 = 0x04;	/* Sequence Error */
+pub(crate) const E1000_RXD_ERR_CXE:u32 = 0x10;	/* Carrier Extension Error */
+pub(crate) const E1000_RXD_ERR_TCPE:u32 = 0x20;	/* TCP/UDP Checksum Error */
+pub(crate) const E1000_RXD_ERR_IPE:u32 = 0x40;	/* IP Checksum Error */
+pub(crate) const E1000_RXD_ERR_RXE:u32 = 0x80;	/* Rx Data Error */
+pub(crate) const E1000_RXD_ERR_FRAME_ERR_MASK:u32 = E1000_RXD_ERR_CE | E1000_RXD_ERR_SE | E1000_RXD_ERR_SEQ | E1000_RXD_ERR_CXE | E1000_RXD_ERR_RXE;
 // pub(crate) const E1000_:u32 = ;	/*  */
 // pub(crate) const E1000_:u32 = ;	/*  */
 // pub(crate) const E1000_:u32 = ;	/*  */
@@ -112,4 +313,14 @@ pub(crate) const E1000_ICR_TXDW:u32 = 0x00000001;	/* Transmit desc written back
 pub(crate) const E1000_ICR_RXDMT0:u32 = 0x00000010;	/* rx desc min. threshold (0) */
 pub(crate) const E1000_ICR_RXSEQ:u32 = 0x00000008;	/* rx sequence error */
 pub(crate) const E1000_ICR_LSC:u32 = 0x00000004;	/* Link Status Change */
+
+/* This defines the bits that are set in the Interrupt Mask
+ * Set/Read Register.  Each bit is documented below:
+ *   o RXT0   = Receiver Timer Interrupt (ring 0)
+ *   o TXDW   = Transmit Descriptor Written Back
+ *   o RXDMT0 = Receive Descriptor Minimum Threshold hit (ring 0)
+ *   o RXSEQ  = Receive Sequence Error
+ *   o LSC    = Link Status Change
+ */
+pub(crate) const IMS_ENABLE_MASK:u32 = E1000_ICR_RXT0 | E1000_ICR_TXDW | E1000_ICR_RXDMT0 | E1000_ICR_RXSEQ | E1000_ICR_LSC;
 // pub(crate) const E1000_:u32 = ;	/*  */
\ No newline at end of file
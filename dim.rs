@@ -0,0 +1,97 @@
+use core::cell::{Cell, UnsafeCell};
+
+use kernel::bindings;
+
+use crate::work::Work;
+
+/// A thin wrapper of the C `struct dim`, the state of the kernel's dynamic interrupt moderation library. The R4L crate
+/// doesn't support it now, so the raw C bindings are used here. `net_dim()` is given a sample after each napi poll,
+/// and schedules the work item when it picks a new profile.
+pub(crate) struct Dim {
+    dim: UnsafeCell<bindings::dim>,
+    // The event counter of the samples, bumped for each of them.
+    events: Cell<u16>,
+}
+
+impl Dim {
+    pub(crate) fn new() -> Self {
+        Self {
+            // SAFETY: `dim` is a plain C struct, all zeros is a valid (but not initialized) value for it.
+            dim: UnsafeCell::new(unsafe{core::mem::zeroed()}),
+            events: Cell::new(0),
+        }
+    }
+
+    /// The work item scheduled by `net_dim()`, it's embedded in the C struct.
+    fn work(&self) -> &Work {
+        // SAFETY: `Work` is a transparent wrapper of `work_struct`.
+        unsafe{&*(core::ptr::addr_of!((*self.dim.get()).work) as *const Work)}
+    }
+
+    /// Find the `Dim` of the work item, for the function given to `init()`.
+    ///
+    /// # Safety
+    ///
+    /// `work` must be the work item of a `Dim`, which is alive for `'a`.
+    pub(crate) unsafe fn from_work<'a>(work: *mut bindings::work_struct) -> &'a Self {
+        // SAFETY: the work item is embedded in a `struct dim`, which is embedded in a `Dim`, as required by the caller.
+        unsafe{
+            let dim = kernel::container_of!(work, bindings::dim, work);
+            &*kernel::container_of!(dim as *const UnsafeCell<bindings::dim>, Dim, dim)
+        }
+    }
+
+    /// Set the function of the work item, which applies the profile picked by `net_dim()`. The moderation period
+    /// starts from the interrupt, same as the e1000 timers.
+    ///
+    /// # Safety
+    ///
+    /// The work item mustn't be pending, and it mustn't be moved until `cancel_sync()` is called.
+    pub(crate) unsafe fn init(&self, func: unsafe extern "C" fn(*mut bindings::work_struct)) {
+        // SAFETY: no one else uses the C struct while the work item is not pending, as required by the caller.
+        unsafe{(*self.dim.get()).mode = bindings::dim_cq_period_mode_DIM_CQ_PERIOD_MODE_START_FROM_EQE as u8};
+        // SAFETY: same as above.
+        unsafe{self.work().init(func)};
+    }
+
+    /// Give `net_dim()` the total number of packets and bytes received so far, corresponding to the C version
+    /// `dim_update_sample()` and `net_dim()`. Must not be called concurrently, the napi poll is the only caller.
+    pub(crate) fn update(&self, packets: u64, bytes: u64) {
+        let events = self.events.get().wrapping_add(1);
+        self.events.set(events);
+
+        // The counters wrap around, `net_dim()` only looks at the differences between two samples.
+        let sample = bindings::dim_sample {
+            // SAFETY: FFI call without preconditions.
+            time: unsafe{bindings::ktime_get()},
+            pkt_ctr: packets as u32,
+            byte_ctr: bytes as u32,
+            event_ctr: events,
+            comp_ctr: 0,
+        };
+        // SAFETY: the C struct is initialized by `init()`, and the napi poll doesn't run concurrently with itself.
+        unsafe{bindings::net_dim(self.dim.get(), sample)};
+    }
+
+    /// The interrupt moderation period of the current profile, in microseconds.
+    pub(crate) fn rx_moderation_usecs(&self) -> u32 {
+        // SAFETY: the C struct is initialized by `init()`, the profile index is only changed by `net_dim()`.
+        let moder = unsafe{
+            let dim = self.dim.get();
+            bindings::net_dim_get_rx_moderation((*dim).mode, (*dim).profile_ix as i32)
+        };
+        moder.usec as u32
+    }
+
+    /// Tell `net_dim()` the new profile has been applied, so it starts measuring again. Called at the end of the work
+    /// item.
+    pub(crate) fn start_measure(&self) {
+        // SAFETY: `net_dim()` doesn't touch the state until it's changed here.
+        unsafe{(*self.dim.get()).state = bindings::dim_state_DIM_START_MEASURE as u8};
+    }
+
+    /// Cancel the work item and wait for it to finish.
+    pub(crate) fn cancel_sync(&self) {
+        self.work().cancel_sync();
+    }
+}
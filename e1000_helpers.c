@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: GPL-2.0
+/*
+ * Wrappers of the static inline functions and macros used by the driver. bindgen can't generate bindings for them,
+ * and the R4L crate doesn't provide helpers for all of them, so they are built into the module. They are declared in
+ * `helpers.rs`.
+ */
+
+#include <linux/dma-mapping.h>
+#include <linux/netdevice.h>
+#include <linux/skbuff.h>
+#include <linux/workqueue.h>
+
+int r4l_e1000_dma_mapping_error(struct device *dev, dma_addr_t dma_addr)
+{
+	return dma_mapping_error(dev, dma_addr);
+}
+
+void r4l_e1000_init_work(struct work_struct *work, work_func_t func)
+{
+	INIT_WORK(work, func);
+}
+
+void r4l_e1000_init_delayed_work(struct delayed_work *dwork, work_func_t func)
+{
+	INIT_DELAYED_WORK(dwork, func);
+}
+
+void r4l_e1000_netif_tx_disable(struct net_device *dev)
+{
+	netif_tx_disable(dev);
+}
+
+struct page *r4l_e1000_dev_alloc_pages(unsigned int order)
+{
+	return dev_alloc_pages(order);
+}
@@ -0,0 +1,1853 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Rust for linux e1000 driver demo
+
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use kernel::pci::Resource;
+use kernel::prelude::*;
+use kernel::sync::Arc;
+use kernel::{pci, device, driver, bindings, net, dma, c_str};
+use kernel::device::RawDevice;
+use kernel::sync::SpinLock;
+
+
+
+// The register and descriptor definitions follow the C headers and the manual, not all of them are used.
+#[allow(dead_code)]
+mod consts;
+#[allow(dead_code)]
+mod hw_defs;
+mod ring_buf;
+mod e1000_ops;
+mod skb;
+mod work;
+mod dim;
+mod helpers;
+
+use hw_defs::TxDescEntry;
+use ring_buf::{RxRingBuf, RxBuffer, TxRingBuf, TxBuffer, TxDma, dma_rmb};
+use skb::{FragDma, RxPage, SkBuffExt};
+
+use e1000_ops::{E1000Ops, E1000LinkStatus, E1000HwStats, E1000Coalesce};
+use work::{Work, DelayedWork};
+use dim::Dim;
+
+use consts::*;
+
+
+module! {
+    type: E1000KernelMod,
+    name: "r4l_e1000_demo",
+    author: "Myrfy001",
+    description: "Rust for linux e1000 driver demo",
+    license: "GPL",
+    params: {
+        interrupt_throttle_rate: u32 {
+            default: 20000,
+            permissions: 0o444,
+            description: "Interrupt Throttling Rate (ints/sec) of the adapters probed afterwards, 0 turns it off, same as the InterruptThrottleRate parameter of the C version",
+        },
+    },
+}
+
+
+/// The private data for this driver
+struct NetDevicePrvData {
+    dev: Arc<device::Device>,
+    napi: Arc<net::Napi>,
+    e1000_hw_ops: Arc<E1000Ops>,
+    tx_ring: SpinLock<Option<TxRingBuf>>,
+    rx_ring: SpinLock<Option<RxRingBuf>>,
+    // Set by `e1000_up()` and cleared by `e1000_down()`, corresponding to the C version `__E1000_DOWN`. The interface
+    // may still be running after a failed restart, then `stop()` has nothing to tear down.
+    up: AtomicBool,
+    // Number of descriptors of the rings allocated by `open()`, only changed with the rtnl lock held.
+    tx_ring_count: AtomicUsize,
+    rx_ring_count: AtomicUsize,
+    irq: u32,
+    // The current MAC address, programmed into RA[0]. The EEPROM one stays in `dev->perm_addr`.
+    mac_addr: SpinLock<[u8; 6]>,
+    irq_handler: AtomicPtr<kernel::irq::Registration<E1000InterruptHandler>>,
+    // The ICR causes collected by the irq handler, consumed by the next napi poll.
+    irq_causes: Arc<AtomicU32>,
+    // The link state last reported to the kernel.
+    link_status: SpinLock<E1000LinkStatus>,
+    // The hardware counters are only 32-bit and cleared on read, they are accumulated here by the watchdog.
+    hw_stats: SpinLock<E1000HwStats>,
+    // Packets dropped by the driver itself, the hardware doesn't know about them.
+    tx_dropped: AtomicU64,
+    rx_dropped: AtomicU64,
+    // Results of the rx checksum offload, shown by `ethtool -S`.
+    rx_csum_good: AtomicU64,
+    rx_csum_err: AtomicU64,
+    // The features programmed into the hardware, the kernel only updates `dev->features` after `set_features()`.
+    features: AtomicU64,
+    // The `E1000_PRIV_FLAG_*` bits set by `ethtool --set-priv-flags`.
+    priv_flags: AtomicU32,
+    // The VLAN IDs added by the stack, same layout as the VLAN filter table. It's written to the hardware again in
+    // `e1000_up()`, since the table is not kept over a reset.
+    active_vlans: SpinLock<[u32; E1000_VLAN_FILTER_TBL_SIZE]>,
+    // The interrupt moderation settings, written to the hardware again in `e1000_up()`.
+    coalesce: SpinLock<E1000Coalesce>,
+    // Extra command bits of the tx data descriptors, corresponding to the C version `adapter->txd_cmd`. It has
+    // `E1000_TXD_CMD_IDE` when the tx interrupt delay is used.
+    txd_cmd: AtomicU32,
+    // The adaptive rx interrupt moderation, fed by the napi poll with the counters below when it's turned on.
+    rx_dim: Dim,
+    total_rx_packets: AtomicU64,
+    total_rx_bytes: AtomicU64,
+    // Runs every `E1000_WATCHDOG_INTERVAL_MS` while the interface is up.
+    watchdog_task: DelayedWork,
+    // The netdev this data belongs to, set in `open()`. The work items only get the data, they find the netdev here.
+    netdev: AtomicPtr<bindings::net_device>,
+    // Scheduled by `e1000_schedule_reset()` to reset the adapter, since that can't be done in the softirq context.
+    reset_task: Work,
+    // The reference to the netdev held while `reset_task` is pending, taken by the task when it runs.
+    reset_netdev: SpinLock<Option<ARef<net::Device>>>,
+    // Number of times the adapter was reset by `e1000_reset_task()` after a tx timeout or hang, shown by `ethtool -S`.
+    // A timeout or hang found while a reset is already pending is not counted again.
+    tx_timeout_count: AtomicU64,
+}
+
+
+// TODO not sure why it is safe to do this.
+unsafe impl Send for NetDevicePrvData {}
+unsafe impl Sync for NetDevicePrvData {}
+
+/// Represent the network device
+struct NetDevice {}
+
+
+impl NetDevice {
+
+    /// Alloc the tx descriptor. But doesn't need to alloc buffer memory, since the network stack will pass in a SkBuff.
+    fn e1000_setup_all_tx_resources(data: &NetDevicePrvData, count: usize) -> Result<TxRingBuf> {
+        // Alloc dma memory space for tx desciptors
+        let dma_desc = dma::Allocation::<hw_defs::TxDescEntry>::try_new(&*data.dev, count, bindings::GFP_KERNEL)?;
+        
+        // Safety: all fields of the slice members will be inited below.
+        let tx_ring = unsafe{core::slice::from_raw_parts_mut(dma_desc.cpu_addr, count)};
+        
+        
+        tx_ring.iter_mut().for_each(|desc| {
+            desc.buf_addr = 0;
+            desc.cmd = 0;
+            desc.length = 0;
+            desc.cso = 0;
+            desc.css = 0;
+            desc.special = 0;
+            desc.sta = 0;
+        });
+        Ok(TxRingBuf::new(dma_desc, count))
+    }
+
+
+    /// Alloc the rx descriptor and the corresponding memory space. use `alloc_skb_ip_align` to alloc buffer and then map it to
+    /// DMA address, buffers larger than 2048 bytes are pages instead.
+    fn e1000_setup_all_rx_resources(dev: &net::Device, data: &NetDevicePrvData, count: usize, rx_buffer_len: usize) -> Result<RxRingBuf> {
+        // Alloc dma memory space for rx desciptors
+        let dma_desc = dma::Allocation::<hw_defs::RxDescEntry>::try_new(&*data.dev, count, bindings::GFP_KERNEL)?;
+        
+        // Safety: all fields of the slice members will be inited below.
+        let rx_ring_desc = unsafe{core::slice::from_raw_parts_mut(dma_desc.cpu_addr, count)};
+
+        rx_ring_desc.iter_mut().for_each(|desc| {
+            desc.buf_addr = 0;
+            desc.length = 0;
+            desc.special = 0;
+            desc.checksum = 0;
+            desc.status = 0;
+            desc.errors = 0;
+        });
+
+        // All the descriptors are filled, but the last one is kept by us so that the ring doesn't look empty.
+        let mut rx_ring = RxRingBuf::new(dma_desc, count);
+        rx_ring.rx_buffer_len = rx_buffer_len;
+        if !Self::e1000_alloc_rx_buffers(dev, data, &mut rx_ring) {
+            return Err(kernel::error::code::ENOMEM);
+        }
+
+        Ok(rx_ring)
+    }
+
+    /// Give new buffers to the empty rx descriptors up to the one before `next_to_clean`, corresponding to the C
+    /// version `e1000_alloc_rx_buffers()`. It stops at the first allocation failure, the rest are retried by the next
+    /// napi poll. The tail register is not written here. Returns whether all of them got a buffer.
+    fn e1000_alloc_rx_buffers(dev: &net::Device, data: &NetDevicePrvData, rx_ring: &mut RxRingBuf) -> bool {
+        let rx_buffer_len = rx_ring.rx_buffer_len;
+
+        while rx_ring.unused_count() > 0 {
+            let idx = rx_ring.next_to_use;
+            if rx_ring.buf.borrow()[idx].is_none() {
+                let buffer = if rx_buffer_len > E1000_RXBUFFER_2048 {
+                    match RxPage::try_new(&*data.dev, rx_buffer_len) {
+                        Ok(page) => RxBuffer::Page(page),
+                        Err(_) => return false,
+                    }
+                } else {
+                    let skb = match dev.alloc_skb_ip_align(rx_buffer_len as u32) {
+                        Ok(skb) => skb,
+                        Err(_) => return false,
+                    };
+                    let dma_map = match dma::MapSingle::try_new(&*data.dev, skb.head_data().as_ptr() as *mut u8, rx_buffer_len, bindings::dma_data_direction_DMA_FROM_DEVICE) {
+                        Ok(dma_map) => dma_map,
+                        Err(_) => return false,
+                    };
+                    RxBuffer::Skb((dma_map, skb))
+                };
+
+                let desc = &mut rx_ring.desc.as_desc_slice()[idx];
+                desc.buf_addr = buffer.dma_handle();
+                desc.status = 0;
+                rx_ring.buf.borrow_mut()[idx] = Some(buffer);
+            }
+            rx_ring.next_to_use = (idx + 1) % rx_ring.count();
+        }
+        true
+    }
+
+
+    // corresponding to the C version e1000_clean_tx_irq()
+    fn e1000_recycle_tx_queue(dev: &net::Device, data: &NetDevicePrvData) {
+        let mut tx_ring = data.tx_ring.lock_irqdisable();
+        let tx_ring = tx_ring.as_mut().unwrap();
+
+        let descs = tx_ring.desc.as_desc_slice();
+        let mut buf = tx_ring.buf.borrow_mut();
+
+        // The DD bit is the only thing we check, the head register is never read. It's only reported in the last
+        // descriptor of a packet, then all the descriptors of the packet are cleaned.
+        let mut idx = tx_ring.next_to_clean;
+        while idx != tx_ring.next_to_use {
+            let eop = buf[idx].as_ref().unwrap().next_to_watch.unwrap();
+            if descs[eop].sta & E1000_TXD_STAT_DD as u8 == 0 {
+                break;
+            }
+            /* read the other descriptors after status DD */
+            dma_rmb();
+
+            loop {
+                let cleaned = idx == eop;
+                let tx_buffer = buf[idx].take().unwrap();
+                if let Some(skb) = tx_buffer.skb {
+                    dev.completed_queue(tx_buffer.segs, tx_buffer.bytecount);
+                    skb.napi_consume(64);
+                }
+                // The mapping is released here.
+                drop(tx_buffer.dma);
+
+                descs[idx].sta = 0;
+                idx = (idx + 1) % descs.len();
+                if cleaned {
+                    break;
+                }
+            }
+        }
+        drop(buf);
+        tx_ring.next_to_clean = idx;
+
+        // `start_xmit()` stops the queue under the same lock, so the wake up can't be missed.
+        if dev.netif_queue_stopped() && dev.netif_carrier_ok() &&
+            tx_ring.unused_count() >= E1000_TX_WAKE_THRESHOLD {
+            dev.netif_wake_queue();
+        }
+    }
+
+    /// Number of descriptors `skb` takes, corresponding to the counting part of the C version `e1000_xmit_frame()`.
+    fn e1000_tx_desc_count(skb: &net::SkBuff) -> usize {
+        let txd_use_count = |len: usize| (len + E1000_MAX_DATA_PER_TXD - 1) / E1000_MAX_DATA_PER_TXD;
+
+        // One more for the context descriptor of checksum offload.
+        let context = if skb.ip_summed() == bindings::CHECKSUM_PARTIAL { 1 } else { 0 };
+
+        context + txd_use_count(skb.head_data().len()) +
+            (0..skb.nr_frags()).map(|i| txd_use_count(skb.frag(i).len)).sum::<usize>()
+    }
+
+    /// Write a context descriptor at `next_to_use` for the checksum offload of a `CHECKSUM_PARTIAL` skb, corresponding
+    /// to the C version `e1000_tx_csum()`. Returns false if the skb doesn't need it, or the checksum offsets don't fit
+    /// in the descriptor and the checksum has been computed by `skb_checksum_help()` instead.
+    fn e1000_tx_csum(tx_ring: &mut TxRingBuf, skb: &net::SkBuff) -> Result<bool> {
+        if skb.ip_summed() != bindings::CHECKSUM_PARTIAL {
+            return Ok(false);
+        }
+
+        let css = skb.csum_start_offset();
+        let cso = css + skb.csum_offset();
+        if cso > E1000_MAX_CSUM_OFFSET {
+            skb.checksum_help()?;
+            return Ok(false);
+        }
+
+        let mut cmd_len = E1000_TXD_CMD_DEXT | E1000_TXD_DTYP_C;
+        if skb.ip_protocol() == Some(bindings::IPPROTO_TCP as u8) {
+            cmd_len |= E1000_TXD_CMD_TCP;
+        }
+
+        let i = tx_ring.next_to_use;
+        let descs = tx_ring.desc.as_desc_slice();
+        let context_desc = descs[i].as_context();
+        // The IP header checksum is computed by the stack, only the TCP/UDP one is offloaded.
+        context_desc.ipcss = 0;
+        context_desc.ipcso = 0;
+        context_desc.ipcse = 0;
+        context_desc.tucss = css as u8;
+        context_desc.tucso = cso as u8;
+        // 0 means to the end of the packet.
+        context_desc.tucse = 0;
+        context_desc.cmd_and_length = cmd_len;
+        context_desc.status = 0;
+        context_desc.hdr_len = 0;
+        context_desc.mss = 0;
+
+        tx_ring.buf.borrow_mut()[i] = Some(TxBuffer{dma: None, skb: None, segs: 0, bytecount: 0, next_to_watch: None});
+        tx_ring.next_to_use = (i + 1) % descs.len();
+
+        Ok(true)
+    }
+
+    /// The folded sum of the TCP pseudo header with a zero length, the hardware adds the length of every segment to it.
+    /// Corresponding to `~csum_tcpudp_magic(saddr, daddr, 0, IPPROTO_TCP, 0)` and `~tcp_v6_check(0, saddr, daddr, 0)`
+    /// in the C version.
+    fn e1000_tso_pseudo_csum(addrs: &[u8]) -> u16 {
+        let mut sum = bindings::IPPROTO_TCP as u32;
+        for word in addrs.chunks(2) {
+            sum += u16::from_be_bytes([word[0], word[1]]) as u32;
+        }
+        while sum >> 16 != 0 {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        sum as u16
+    }
+
+    /// Write a TCP segmentation context descriptor at `next_to_use` for a GSO skb, corresponding to the C version
+    /// `e1000_tso()`. Returns the length of the headers, which are repeated in every segment, or None if the skb is
+    /// not a GSO one.
+    fn e1000_tso(tx_ring: &mut TxRingBuf, skb: &net::SkBuff) -> Result<Option<usize>> {
+        if skb.gso_size() == 0 {
+            return Ok(None);
+        }
+
+        let ipv4 = skb.gso_type() & bindings::SKB_GSO_TCPV4 != 0;
+        let ipcss = skb.network_offset();
+        let tucss = skb.transport_offset();
+        let tucso = tucss + 16;	/* offset of `check` in `struct tcphdr` */
+        let hdr_len = skb.tcp_all_headers();
+        if tucso > E1000_MAX_CSUM_OFFSET || hdr_len > E1000_MAX_CSUM_OFFSET {
+            return Err(kernel::error::code::EINVAL);
+        }
+
+        // The headers are changed below, they may be shared with a clone.
+        skb.cow_head()?;
+
+        // The hardware fills in the length fields and the checksums of every segment, they must start from zero.
+        let mut cmd_len = E1000_TXD_CMD_DEXT | E1000_TXD_CMD_TSE | E1000_TXD_CMD_TCP;
+        let (ipcso, ipcse);
+        if ipv4 {
+            let csum = Self::e1000_tso_pseudo_csum(&skb.head_data()[ipcss + 12..ipcss + 20]);
+            skb.write_be16(ipcss + 2, 0);	/* tot_len */
+            skb.write_be16(ipcss + 10, 0);	/* check */
+            skb.write_be16(tucso, csum);
+            cmd_len |= E1000_TXD_CMD_IP;
+            ipcso = ipcss + 10;
+            ipcse = tucss - 1;
+        } else {
+            let csum = Self::e1000_tso_pseudo_csum(&skb.head_data()[ipcss + 8..ipcss + 40]);
+            skb.write_be16(ipcss + 4, 0);	/* payload_len */
+            skb.write_be16(tucso, csum);
+            ipcso = 0;
+            ipcse = 0;
+        }
+        cmd_len |= skb.len() - hdr_len as u32;
+
+        let i = tx_ring.next_to_use;
+        let descs = tx_ring.desc.as_desc_slice();
+        let context_desc = descs[i].as_context();
+        context_desc.ipcss = ipcss as u8;
+        context_desc.ipcso = ipcso as u8;
+        context_desc.ipcse = ipcse as u16;
+        context_desc.tucss = tucss as u8;
+        context_desc.tucso = tucso as u8;
+        context_desc.tucse = 0;
+        context_desc.cmd_and_length = cmd_len;
+        context_desc.status = 0;
+        context_desc.hdr_len = hdr_len as u8;
+        context_desc.mss = skb.gso_size() as u16;
+
+        tx_ring.buf.borrow_mut()[i] = Some(TxBuffer{dma: None, skb: None, segs: 0, bytecount: 0, next_to_watch: None});
+        tx_ring.next_to_use = (i + 1) % descs.len();
+
+        Ok(Some(hdr_len))
+    }
+
+    /// Map the linear part and the page frags of `skb` to consecutive descriptors from `next_to_use`, buffers larger
+    /// than `E1000_MAX_DATA_PER_TXD` are split. Corresponding to the C version `e1000_tx_map()`. The flags of the last
+    /// descriptor and `next_to_use` are left to the caller, its index is returned. If anything can't be mapped, the
+    /// buffers mapped so far are released.
+    fn e1000_tx_map(data: &NetDevicePrvData, tx_ring: &mut TxRingBuf, skb: &net::SkBuff, tx_flags: u32) -> Result<usize> {
+        let descs = tx_ring.desc.as_desc_slice();
+        let mut buf = tx_ring.buf.borrow_mut();
+
+        // Corresponding to `txd_lower` and `txd_upper` of the C version `e1000_tx_queue()`.
+        let (mut cmd, mut dtyp, mut popts, mut special) = (data.txd_cmd.load(Ordering::Relaxed), 0, 0, 0);
+        if tx_flags & E1000_TX_FLAGS_TSO != 0 {
+            cmd |= E1000_TXD_CMD_DEXT | E1000_TXD_CMD_TSE;
+            dtyp |= E1000_TXD_DTYP_D;
+            popts |= E1000_TXD_POPTS_TXSM;
+            if tx_flags & E1000_TX_FLAGS_IPV4 != 0 {
+                popts |= E1000_TXD_POPTS_IXSM;
+            }
+        }
+        if tx_flags & E1000_TX_FLAGS_CSUM != 0 {
+            cmd |= E1000_TXD_CMD_DEXT;
+            dtyp |= E1000_TXD_DTYP_D;
+            popts |= E1000_TXD_POPTS_TXSM;
+        }
+        if tx_flags & E1000_TX_FLAGS_VLAN != 0 {
+            cmd |= E1000_TXD_CMD_VLE;
+            special = (tx_flags & E1000_TX_FLAGS_VLAN_MASK) >> E1000_TX_FLAGS_VLAN_SHIFT;
+        }
+
+        let first = tx_ring.next_to_use;
+        let mut idx = first;
+        let mut last = first;
+        let mut ret = Ok(());
+
+        // Buffer 0 is the linear part, the others are the page frags.
+        'map: for i in 0..=skb.nr_frags() {
+            let len = if i == 0 { skb.head_data().len() } else { skb.frag(i - 1).len };
+
+            let mut offset = 0;
+            while offset < len {
+                let size = core::cmp::min(len - offset, E1000_MAX_DATA_PER_TXD);
+                let dma = if i == 0 {
+                    let addr = skb.head_data()[offset..].as_ptr() as *mut u8;
+                    dma::MapSingle::try_new(&*data.dev, addr, size, bindings::dma_data_direction_DMA_TO_DEVICE).map(TxDma::Single)
+                } else {
+                    FragDma::try_new(&*data.dev, &skb.frag(i - 1), offset, size).map(TxDma::Frag)
+                };
+                let dma = match dma {
+                    Ok(dma) => dma,
+                    Err(e) => {
+                        ret = Err(e);
+                        break 'map;
+                    }
+                };
+
+                // In the extended form, `cso` holds the descriptor type and `css` the packet options.
+                descs[idx].buf_addr = dma.dma_handle();
+                descs[idx].length = size as u16;
+                descs[idx].cso = (dtyp >> 16) as u8;
+                descs[idx].cmd = (cmd >> 24) as u8;
+                descs[idx].sta = 0;
+                descs[idx].css = popts as u8;
+                descs[idx].special = special as u16;
+                buf[idx] = Some(TxBuffer{dma: Some(dma), skb: None, segs: 0, bytecount: 0, next_to_watch: None});
+
+                last = idx;
+                idx = (idx + 1) % descs.len();
+                offset += size;
+            }
+        }
+
+        if let Err(e) = ret {
+            while idx != first {
+                idx = (idx + descs.len() - 1) % descs.len();
+                buf[idx] = None;
+            }
+            return Err(e);
+        }
+
+        Ok(last)
+    }
+
+    /// Write the tail register unless the stack has more packets for us, corresponding to the doorbell part of the C
+    /// version `e1000_xmit_frame()`. If the queue is stopped no more packets will come, so the batch is flushed now.
+    fn e1000_tx_doorbell(dev: &net::Device, data: &NetDevicePrvData, tx_ring: &TxRingBuf) {
+        if !net::netdev_xmit_more() || dev.netif_queue_stopped() {
+            data.e1000_hw_ops.e1000_write_tx_queue_tail(tx_ring.next_to_use as u32);
+        }
+    }
+
+    /// Free a packet that can't be transmitted. The stack is told it has been sent, so it won't be requeued.
+    fn e1000_drop_tx_skb(skb: &net::SkBuff, dev: &net::Device, data: &NetDevicePrvData, tx_ring: &TxRingBuf) -> net::NetdevTx {
+        // budget 0 means we are not in napi context.
+        skb.napi_consume(0);
+        data.tx_dropped.fetch_add(1, Ordering::Relaxed);
+
+        // This may be the last packet of a batch, the packets queued before it still need the doorbell.
+        Self::e1000_tx_doorbell(dev, data, tx_ring);
+        net::NetdevTx::Ok
+    }
+
+    /// Report the checksum offload result of a received packet to the stack, corresponding to the C version
+    /// `e1000_rx_checksum()`. In `csum_complete` mode the raw packet checksum, which starts at the network header, is
+    /// passed up instead, so the stack can verify any protocol with it, see `e1000_rx_csum_complete()`. The counters
+    /// follow the IP and TCP/UDP checks of the hardware in both modes.
+    fn e1000_rx_checksum(data: &NetDevicePrvData, skb: &net::SkBuff, status: u32, errors: u32, checksum: u16, csum_complete: bool) {
+        if csum_complete {
+            // The hardware complements the packet checksum, undo it and put it in network order, same as the C version.
+            skb.set_csum_complete((!checksum).to_be() as u32);
+        }
+
+        // Ignore Checksum bit is set
+        if status & E1000_RXD_STAT_IXSM != 0 {
+            return;
+        }
+
+        // IP or TCP/UDP checksum error bit is set
+        if errors & (E1000_RXD_ERR_IPE | E1000_RXD_ERR_TCPE) != 0 {
+            data.rx_csum_err.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        // TCP/UDP Checksum has not been calculated
+        if status & E1000_RXD_STAT_TCPCS == 0 {
+            return;
+        }
+
+        // It must be a TCP or UDP packet with a valid checksum, and a valid IP checksum if IPCS is set.
+        if !csum_complete {
+            skb.set_csum_unnecessary();
+        }
+        data.rx_csum_good.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether `e1000_rx_checksum()` passes the raw packet checksum up as `CHECKSUM_COMPLETE` instead of marking the
+    /// verified TCP/UDP packets `CHECKSUM_UNNECESSARY`. It's off by default and turned on per device with
+    /// `ethtool --set-priv-flags eth0 rx-csum-complete on`. Like the other mode it only applies while rx checksumming
+    /// is on, `ethtool -K eth0 rx off` turns both of them off.
+    fn e1000_rx_csum_complete(data: &NetDevicePrvData, features: u64) -> bool {
+        features & NETIF_F_RXCSUM != 0 && data.priv_flags.load(Ordering::Relaxed) & E1000_PRIV_FLAG_RX_CSUM_COMPLETE != 0
+    }
+
+    /// Clean at most `budget` rx descriptors, corresponding to the C versions `e1000_clean_rx_irq()` and
+    /// `e1000_clean_jumbo_rx_irq()`. With page buffers a frame may span several descriptors, each of them counts
+    /// against the budget. Returns the number of descriptors cleaned.
+    fn e1000_clean_rx_irq(dev: &net::Device, data: &NetDevicePrvData, budget: i32) -> i32 {
+        let mut rx_ring_guard = data.rx_ring.lock();
+        let rx_ring =  rx_ring_guard.as_mut().unwrap();
+
+        let descs = rx_ring.desc.as_desc_slice();
+        let features = data.features.load(Ordering::Relaxed);
+        let rx_csum = features & NETIF_F_RXCSUM != 0;
+        let rx_vlan = features & NETIF_F_HW_VLAN_CTAG_RX != 0;
+        let csum_complete = Self::e1000_rx_csum_complete(data, features);
+
+        let mut idx = rx_ring.next_to_clean;
+        let mut work_done = 0;
+        while work_done < budget && descs[idx].status & E1000_RXD_STAT_DD as u8 != 0 {
+            /* read descriptor and rx_buffer_info after status DD */
+            dma_rmb();
+
+            let status = descs[idx].status as u32;
+            let errors = descs[idx].errors as u32;
+            let length = descs[idx].length as u32;
+            let checksum = descs[idx].checksum;
+            let special = descs[idx].special;
+
+            // The buffer is unmapped before the data is touched. The descriptor is refilled after the loop.
+            let buffer = rx_ring.buf.borrow_mut()[idx].take().unwrap();
+            descs[idx].status = 0;
+
+            idx = (idx + 1) % descs.len();
+            work_done += 1;
+
+            let eop = status & E1000_RXD_STAT_EOP != 0;
+
+            // A part of this frame has been dropped, so are the rest of it.
+            if rx_ring.discarding {
+                rx_ring.discarding = !eop;
+                continue;
+            }
+
+            // The errors are only valid in the last descriptor, the parts received before are dropped too.
+            if eop && errors & E1000_RXD_ERR_FRAME_ERR_MASK != 0 {
+                rx_ring.rx_skb_top = None;
+                data.rx_dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
+            let skb = match buffer {
+                RxBuffer::Skb((dma_map, skb)) => {
+                    drop(dma_map);
+
+                    // These buffers hold a whole frame of the MTU. Their skb heads may not be page frags, so a longer
+                    // frame that spans several of them can't be chained and is dropped, same as the C version
+                    // `e1000_clean_rx_irq()`.
+                    if !eop {
+                        data.rx_dropped.fetch_add(1, Ordering::Relaxed);
+                        rx_ring.discarding = true;
+                        continue;
+                    }
+                    skb.put(length);
+                    skb
+                }
+                RxBuffer::Page(page) => {
+                    // Every part of the frame is a page frag, the linear part is filled with the header at the end.
+                    let top = match rx_ring.rx_skb_top.take() {
+                        Some(top) => top,
+                        None => match dev.alloc_skb_ip_align(E1000_RX_HDR_LEN as u32) {
+                            Ok(top) => top,
+                            Err(_) => {
+                                data.rx_dropped.fetch_add(1, Ordering::Relaxed);
+                                rx_ring.discarding = !eop;
+                                continue;
+                            }
+                        },
+                    };
+                    // LPE limits the frames to 16384 bytes, which is at most 4 buffers, but check it anyway.
+                    if top.nr_frags() >= MAX_SKB_FRAGS {
+                        data.rx_dropped.fetch_add(1, Ordering::Relaxed);
+                        rx_ring.discarding = !eop;
+                        continue;
+                    }
+                    page.add_to_skb(&top, length as usize);
+                    top
+                }
+            };
+
+            if !eop {
+                rx_ring.rx_skb_top = Some(skb);
+                continue;
+            }
+
+            // `eth_type_trans()` reads the Ethernet header from the linear part.
+            if skb.may_pull(ENET_HEADER_SIZE).is_err() {
+                data.rx_dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
+            let protocol = skb.eth_type_trans(dev);
+            skb.protocol_set(protocol);
+
+            // The checksum and VLAN fields are only valid in the last descriptor.
+            if rx_csum {
+                Self::e1000_rx_checksum(data, &skb, status, errors, checksum, csum_complete);
+            }
+            if rx_vlan && status & E1000_RXD_STAT_VP != 0 {
+                skb.vlan_hwaccel_put_tag(bindings::ETH_P_8021Q as u16, special);
+            }
+
+            data.total_rx_packets.fetch_add(1, Ordering::Relaxed);
+            data.total_rx_bytes.fetch_add(skb.len() as u64, Ordering::Relaxed);
+            data.napi.gro_receive(&skb);
+        }
+
+        rx_ring.next_to_clean = idx;
+
+        // Give all the refilled descriptors back with a single tail write. It's also done when nothing is cleaned,
+        // the buffers that couldn't be allocated last time are retried here.
+        let next_to_use = rx_ring.next_to_use;
+        Self::e1000_alloc_rx_buffers(dev, data, rx_ring);
+        if rx_ring.next_to_use != next_to_use {
+            // `writel()` makes the refilled descriptors visible to the device before the tail moves.
+            data.e1000_hw_ops.e1000_write_rx_queue_tail(rx_ring.next_to_use as u32);
+        }
+
+        work_done
+    }
+
+    /// Program the hardware with the given rings and start the interface. It's the body of `open()`, and is also used
+    /// to restart the interface after its configuration is changed. The rings are allocated by the caller, so that a
+    /// restart can give up before the old rings are released. If it fails, everything is released and the interface
+    /// is left down.
+    fn e1000_up(dev: &net::Device, data: &NetDevicePrvData, tx_ringbuf: TxRingBuf, rx_ringbuf: RxRingBuf) -> Result {
+        dev.netif_carrier_off();
+
+        // TODO e1000_power_up_phy() not implemented. It's used in case of PHY *MAY* power down,
+        // which will not be supported in this MVP driver.
+        
+
+        // modify e1000's hardware registers, give rx/tx queue info to the nic. The rings may already be in use by the
+        // hardware if it fails halfway, so it's reset before they are released.
+        if let Err(e) = Self::e1000_configure(dev, data, &tx_ringbuf, &rx_ringbuf) {
+            let _ = data.e1000_hw_ops.e1000_reset_hw();
+            return Err(e);
+        }
+
+        *data.rx_ring.lock_irqdisable() = Some(rx_ringbuf);
+        *data.tx_ring.lock_irqdisable() = Some(tx_ringbuf);
+
+        if let Err(e) = Self::e1000_request_irq(data) {
+            let _ = data.e1000_hw_ops.e1000_reset_hw();
+            Self::e1000_free_all_resources(dev, data);
+            return Err(e);
+        }
+
+        data.up.store(true, Ordering::Relaxed);
+        data.napi.enable();
+        data.e1000_hw_ops.e1000_irq_enable();
+
+        dev.netif_start_queue();
+
+        // The carrier stays off until the link is reported up by the hardware.
+        *data.link_status.lock_irqdisable() = E1000LinkStatus::default();
+        Self::e1000_update_link(dev, data);
+
+        // SAFETY: the work item is not pending since `e1000_down()` cancels it, and `data` is not moved while the
+        // netdev lives.
+        unsafe{data.watchdog_task.init(e1000_watchdog)};
+        data.watchdog_task.schedule_msecs(E1000_WATCHDOG_INTERVAL_MS);
+
+        Ok(())
+    }
+
+    /// Program the rings and all the settings kept over a reset into the hardware, corresponding to the C version
+    /// `e1000_configure()`.
+    fn e1000_configure(dev: &net::Device, data: &NetDevicePrvData, tx_ringbuf: &TxRingBuf, rx_ringbuf: &RxRingBuf) -> Result {
+        let max_frame_size = Self::e1000_max_frame_size(dev.mtu_get() as usize);
+        let mac_addr = *data.mac_addr.lock_irqdisable();
+        data.e1000_hw_ops.e1000_configure(rx_ringbuf, tx_ringbuf, &mac_addr, max_frame_size, data.features.load(Ordering::Relaxed))?;
+
+        let coalesce = *data.coalesce.lock_irqdisable();
+        data.e1000_hw_ops.e1000_set_coalesce(&coalesce)?;
+        if coalesce.adaptive_rx {
+            Self::e1000_set_rx_dim_moderation(data)?;
+        }
+        Self::e1000_restore_vlan(data)?;
+        Self::e1000_set_rx_mode(dev, data)
+    }
+
+    /// Quiesce the hardware and release everything allocated by `e1000_up()`. It's the body of `stop()`. Nothing is
+    /// done if the interface is already down, so the napi is never disabled twice.
+    fn e1000_down(dev: &net::Device, data: &NetDevicePrvData) -> Result {
+        if !data.up.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // corresponding to the C version `e1000_down()`
+        data.watchdog_task.cancel_sync();
+        dev.netif_carrier_off();
+        // Unlike `netif_stop_queue()`, this waits for a `start_xmit()` running on another CPU, the rings are released
+        // below.
+        // SAFETY: `dev` is a valid netdev, `net::Device` wraps the C struct.
+        unsafe{helpers::r4l_e1000_netif_tx_disable(dev as *const net::Device as *mut bindings::net_device)};
+
+        // Keep tearing down even if the registers can't be accessed, otherwise the next `open()` will fail.
+        let ret = data.e1000_hw_ops.e1000_disable_rx_tx();
+
+        data.napi.disable();
+        // Nothing schedules it once the napi is disabled.
+        data.rx_dim.cancel_sync();
+        data.e1000_hw_ops.e1000_irq_disable();
+
+        // corresponding to the C version `e1000_close()`
+        Self::e1000_free_irq(data);
+
+        // The statistics registers are cleared by the reset, save them first.
+        Self::e1000_update_stats(data);
+
+        // Reset the MAC so that it stops DMA before the buffers are unmapped and freed.
+        let ret = ret.and(data.e1000_hw_ops.e1000_reset_hw());
+        Self::e1000_free_all_resources(dev, data);
+
+        ret
+    }
+
+    /// Allocate the rings of the current configuration, corresponding to the C version `e1000_setup_all_tx_resources()`
+    /// and `e1000_setup_all_rx_resources()`.
+    fn e1000_setup_all_resources(dev: &net::Device, data: &NetDevicePrvData) -> Result<(TxRingBuf, RxRingBuf)> {
+        let tx_ringbuf = Self::e1000_setup_all_tx_resources(data, data.tx_ring_count.load(Ordering::Relaxed))?;
+        let rx_buffer_len = Self::e1000_rx_buffer_len(dev.mtu_get() as usize);
+        let rx_ringbuf = Self::e1000_setup_all_rx_resources(dev, data, data.rx_ring_count.load(Ordering::Relaxed), rx_buffer_len)?;
+        Ok((tx_ringbuf, rx_ringbuf))
+    }
+
+    /// Switch to rings of `tx_count` and `rx_count` descriptors and to `mtu`, corresponding to the C version
+    /// `e1000_set_ringparam()` and `e1000_change_mtu()`. If the interface is running, the new rings are allocated
+    /// first and nothing is changed if that fails. Then the interface is stopped and started again with them. Must be
+    /// called with the rtnl lock held, same as `open()` and `stop()`.
+    fn e1000_reinit(dev: &net::Device, data: &NetDevicePrvData, tx_count: usize, rx_count: usize, mtu: u32) -> Result {
+        if !dev.netif_running() {
+            data.tx_ring_count.store(tx_count, Ordering::Relaxed);
+            data.rx_ring_count.store(rx_count, Ordering::Relaxed);
+            dev.set_mtu(mtu);
+            return Ok(());
+        }
+
+        let tx_ringbuf = Self::e1000_setup_all_tx_resources(data, tx_count)?;
+        let rx_ringbuf = Self::e1000_setup_all_rx_resources(dev, data, rx_count, Self::e1000_rx_buffer_len(mtu as usize))?;
+        data.tx_ring_count.store(tx_count, Ordering::Relaxed);
+        data.rx_ring_count.store(rx_count, Ordering::Relaxed);
+        dev.set_mtu(mtu);
+
+        // The interface is torn down completely even if the registers can't be accessed, go on to start it again.
+        if let Err(e) = Self::e1000_down(dev, data) {
+            pr_err!("Failed to stop the adapter: {:?}\n", e);
+        }
+        Self::e1000_up(dev, data, tx_ringbuf, rx_ringbuf)
+    }
+
+    /// Write all the active VLAN IDs into the VLAN filter table, corresponding to the C version `e1000_restore_vlan()`.
+    fn e1000_restore_vlan(data: &NetDevicePrvData) -> Result {
+        let active_vlans = data.active_vlans.lock_irqdisable();
+        for (offset, value) in active_vlans.iter().enumerate() {
+            data.e1000_hw_ops.e1000_write_vfta(offset, *value)?;
+        }
+        Ok(())
+    }
+
+    /// Filter the VLAN IDs if the feature is on, except in promiscuous mode where everything is received.
+    fn e1000_update_vlan_filter(dev: &net::Device, data: &NetDevicePrvData, features: u64) -> Result {
+        let promisc = dev.flags() & bindings::net_device_flags_IFF_PROMISC != 0;
+        data.e1000_hw_ops.e1000_vlan_filter_on_off(features & NETIF_F_HW_VLAN_CTAG_FILTER != 0 && !promisc)
+    }
+
+    /// Program the address filters and the promiscuous modes from the netdev, corresponding to the C version
+    /// `e1000_set_rx_mode()`.
+    fn e1000_set_rx_mode(dev: &net::Device, data: &NetDevicePrvData) -> Result {
+        let flags = dev.flags();
+        let promisc = flags & bindings::net_device_flags_IFF_PROMISC != 0;
+        let allmulti = flags & bindings::net_device_flags_IFF_ALLMULTI != 0;
+
+        data.e1000_hw_ops.e1000_set_rx_mode(promisc, allmulti, &mut dev.uc_addrs(), &mut dev.mc_addrs())?;
+        Self::e1000_update_vlan_filter(dev, data, data.features.load(Ordering::Relaxed))
+    }
+
+    /// Add or remove `vid` from the software table and the hardware one, corresponding to the C version
+    /// `e1000_vlan_rx_add_vid()` and `e1000_vlan_rx_kill_vid()`.
+    fn e1000_vfta_set(data: &NetDevicePrvData, vid: u16, add: bool) -> Result {
+        if vid >= VLAN_N_VID {
+            return Err(kernel::error::code::EINVAL);
+        }
+
+        let index = (vid >> 5) as usize;
+        let mask = 1 << (vid & 0x1F);
+
+        let mut active_vlans = data.active_vlans.lock_irqdisable();
+        if add {
+            active_vlans[index] |= mask;
+        } else {
+            active_vlans[index] &= !mask;
+        }
+        data.e1000_hw_ops.e1000_write_vfta(index, active_vlans[index])
+    }
+
+    /// The largest frame for `mtu`, with room for a VLAN tag.
+    fn e1000_max_frame_size(mtu: usize) -> usize {
+        mtu + ENET_HEADER_SIZE + ETHERNET_FCS_SIZE + VLAN_TAG_SIZE
+    }
+
+    /// Program the moderation of the current `net_dim()` profile, corresponding to the C version `e1000_set_itr()`.
+    /// The period of the profile goes to ITR, RDTR is turned off so that it doesn't delay the low latency profiles
+    /// even more.
+    fn e1000_set_rx_dim_moderation(data: &NetDevicePrvData) -> Result {
+        let usecs = data.rx_dim.rx_moderation_usecs();
+        let ints_per_sec = if usecs == 0 { 0 } else { 1_000_000 / usecs };
+        data.e1000_hw_ops.e1000_set_rx_moderation(0, ints_per_sec)
+    }
+
+    /// The tx descriptors only wait for the tx delay timers with `E1000_TXD_CMD_IDE`, corresponding to the C version
+    /// `e1000_configure_tx()`.
+    fn e1000_txd_cmd(coalesce: &E1000Coalesce) -> u32 {
+        if coalesce.tx_usecs != 0 {
+            E1000_TXD_CMD_IDE
+        } else {
+            0
+        }
+    }
+
+    /// Pick the smallest rx buffer that holds a whole frame of `mtu` bytes, corresponding to the buffer size part of the
+    /// C version `e1000_change_mtu()`. The buffers larger than 2048 bytes are pages, see `RxPage`, and frames longer
+    /// than the buffers, which LPE lets in up to 16384 bytes, are chained by `e1000_clean_rx_irq()`.
+    fn e1000_rx_buffer_len(mtu: usize) -> usize {
+        let max_frame = Self::e1000_max_frame_size(mtu);
+
+        [
+            E1000_RXBUFFER_256,
+            E1000_RXBUFFER_512,
+            E1000_RXBUFFER_1024,
+            E1000_RXBUFFER_2048,
+            E1000_RXBUFFER_4096,
+            E1000_RXBUFFER_8192,
+        ].into_iter().find(|&len| max_frame <= len).unwrap_or(E1000_RXBUFFER_16384)
+    }
+
+    /// Register the interrupt handler, the registration is kept in `irq_handler` until `e1000_free_irq()` is called.
+    fn e1000_request_irq(data: &NetDevicePrvData) -> Result {
+        let irq_prv_data = Box::try_new(IrqPrivateData{
+            e1000_hw_ops: Arc::clone(&data.e1000_hw_ops),
+            napi: Arc::clone(&data.napi),
+            irq_causes: Arc::clone(&data.irq_causes),
+        })?;
+
+        // Again, the `irq::Registration` contains an `irq::InternalRegistration` which implemented `Drop`, so 
+        // we mustn't let it dropped until `e1000_free_irq()`.
+        let req_reg = kernel::irq::Registration::<E1000InterruptHandler>::try_new(data.irq, irq_prv_data, kernel::irq::flags::SHARED, fmt!("{}",data.dev.name()))?;
+        data.irq_handler.store(Box::into_raw(Box::try_new(req_reg)?), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Unregister the interrupt handler, corresponding to the C version `e1000_free_irq()`.
+    fn e1000_free_irq(data: &NetDevicePrvData) {
+        let irq_reg = data.irq_handler.swap(core::ptr::null_mut(), Ordering::Relaxed);
+        if !irq_reg.is_null() {
+            // SAFETY: the pointer is created by `Box::into_raw` in `e1000_request_irq()`, and it has been swapped out
+            // above, so no one else can free it again. Dropping the registration calls `free_irq()`, which also waits
+            // for any running handler to finish.
+            drop(unsafe{Box::from_raw(irq_reg)});
+        }
+    }
+
+    /// Release the tx and rx rings, corresponding to the C version `e1000_free_all_tx_resources()` and
+    /// `e1000_free_all_rx_resources()`. Dropping a `RingBuf` unmaps and frees all the SkBuffs still attached to it,
+    /// and then frees the descriptor memory.
+    fn e1000_free_all_resources(dev: &net::Device, data: &NetDevicePrvData) {
+        // Take the rings out first, the DMA memory mustn't be freed with the spinlock held and irq disabled.
+        let tx_ring = data.tx_ring.lock_irqdisable().take();
+        let rx_ring = data.rx_ring.lock_irqdisable().take();
+
+        // The pending tx SkBuffs also hold the reference of the stack, which is only released by `napi_consume()`,
+        // same as `e1000_unmap_and_free_tx_resource()` of the C version. Dropping the ring releases our own one.
+        if let Some(tx_ring) = &tx_ring {
+            for tx_buffer in tx_ring.buf.borrow().iter().flatten() {
+                if let Some(skb) = &tx_buffer.skb {
+                    skb.napi_consume(0);
+                }
+            }
+        }
+        drop(tx_ring);
+        drop(rx_ring);
+
+        // The pending tx SkBuffs are freed without `completed_queue()`, so the BQL state must be reset.
+        dev.reset_queue();
+    }
+
+    /// Accumulate the hardware statistics registers into `hw_stats`.
+    fn e1000_update_stats(data: &NetDevicePrvData) {
+        let mut hw_stats = data.hw_stats.lock_irqdisable();
+        if let Err(e) = data.e1000_hw_ops.e1000_update_stats(&mut hw_stats) {
+            pr_err!("Failed to read the statistics registers: {:?}\n", e);
+        }
+    }
+
+    /// Read the link state from the hardware and report changes to the kernel, corresponding to the link part of the
+    /// C version `e1000_watchdog()`.
+    fn e1000_update_link(dev: &net::Device, data: &NetDevicePrvData) {
+        let link = match data.e1000_hw_ops.e1000_check_link() {
+            Ok(link) => link,
+            Err(e) => {
+                pr_err!("Failed to read the link status: {:?}\n", e);
+                return;
+            }
+        };
+
+        let mut link_status = data.link_status.lock_irqdisable();
+        if *link_status == link {
+            return;
+        }
+
+        if link.up {
+            pr_info!("NIC Link is Up {} Mbps {} Duplex\n", link.speed, if link.full_duplex {"Full"} else {"Half"});
+            dev.netif_carrier_on();
+        } else {
+            pr_info!("NIC Link is Down\n");
+            dev.netif_carrier_off();
+        }
+        *link_status = link;
+    }
+
+    /// Schedule `e1000_reset_task()`, corresponding to `schedule_work(&adapter->reset_task)` of the C version. The
+    /// task can't be cancelled in `stop()` since it takes the rtnl lock, so a reference to the netdev is held until it
+    /// has run instead. `unregister_netdev()` waits for it, the data is not freed under the task. Nothing is scheduled
+    /// once `e1000_down()` has begun, the interface is being stopped or reset already.
+    fn e1000_schedule_reset(dev: &net::Device, data: &NetDevicePrvData) {
+        if !data.up.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut reset_netdev = data.reset_netdev.lock_irqdisable();
+        if reset_netdev.is_none() && data.reset_task.schedule() {
+            *reset_netdev = Some(ARef::from(dev));
+        }
+    }
+
+    /// Print the state of the tx ring when it hangs, corresponding to the C version `e1000_clean_tx_irq()` and the tx
+    /// part of `e1000_dump()`.
+    fn e1000_dump_tx_ring(data: &NetDevicePrvData, tx_ring: &mut TxRingBuf) {
+        let next_to_use = tx_ring.next_to_use;
+        let next_to_clean = tx_ring.next_to_clean;
+        let eop = tx_ring.buf.borrow()[next_to_clean].as_ref().and_then(|tx_buffer| tx_buffer.next_to_watch);
+        let descs = tx_ring.desc.as_desc_slice();
+
+        pr_err!("Detected Tx Unit Hang\n");
+        pr_err!("  TDH                  <{:x}>\n", data.e1000_hw_ops.e1000_read_tx_queue_head());
+        pr_err!("  TDT                  <{:x}>\n", data.e1000_hw_ops.e1000_read_tx_queue_tail());
+        pr_err!("  next_to_use          <{:x}>\n", next_to_use);
+        pr_err!("  next_to_clean        <{:x}>\n", next_to_clean);
+        if let Some(eop) = eop {
+            pr_err!("  next_to_watch        <{:x}>\n", eop);
+            pr_err!("  next_to_watch.cmd    <{:x}>\n", descs[eop].cmd);
+            pr_err!("  next_to_watch.status <{:x}>\n", descs[eop].sta);
+        }
+    }
+
+    /// Check if the tx ring has stopped moving, corresponding to the hang detection of the C version
+    /// `e1000_clean_tx_irq()`. It's hung if the oldest pending packet is still not done a whole watchdog interval
+    /// later, unless the link is down or the transmission is paused by flow control. The ring is dumped if so.
+    fn e1000_check_tx_hang(dev: &net::Device, data: &NetDevicePrvData) -> bool {
+        let mut tx_ring = data.tx_ring.lock_irqdisable();
+        let tx_ring = match tx_ring.as_mut() {
+            Some(tx_ring) => tx_ring,
+            None => return false,
+        };
+
+        let next_to_clean = tx_ring.next_to_clean;
+        if next_to_clean == tx_ring.next_to_use {
+            tx_ring.watchdog_next_to_clean = None;
+            return false;
+        }
+        if tx_ring.watchdog_next_to_clean.replace(next_to_clean) != Some(next_to_clean) {
+            return false;
+        }
+
+        // The packet may be done but not cleaned yet, the napi poll will get to it.
+        let eop = tx_ring.buf.borrow()[next_to_clean].as_ref().and_then(|tx_buffer| tx_buffer.next_to_watch);
+        if let Some(eop) = eop {
+            if tx_ring.desc.as_desc_slice()[eop].sta & E1000_TXD_STAT_DD as u8 != 0 {
+                return false;
+            }
+        }
+
+        if !dev.netif_carrier_ok() || data.e1000_hw_ops.e1000_tx_paused().unwrap_or(true) {
+            return false;
+        }
+
+        Self::e1000_dump_tx_ring(data, tx_ring);
+        true
+    }
+
+
+}
+
+#[vtable]
+impl net::DeviceOperations for NetDevice {
+    
+    type Data = Box<NetDevicePrvData>;
+
+    /// this method will be called when you type `ip link set eth0 up` in your shell.
+    fn open(dev: &net::Device, data: &NetDevicePrvData) -> Result {
+        pr_info!("Rust for linux e1000 driver demo (net device open)\n");
+
+        data.netdev.store(dev as *const net::Device as *mut bindings::net_device, Ordering::Relaxed);
+        // SAFETY: the work item is not pending since `e1000_down()` cancels it, and `data` is not moved while the
+        // netdev lives.
+        unsafe{data.rx_dim.init(e1000_rx_dim_work)};
+
+        let (tx_ringbuf, rx_ringbuf) = Self::e1000_setup_all_resources(dev, data)?;
+        Self::e1000_up(dev, data, tx_ringbuf, rx_ringbuf)
+    }
+
+    /// this method will be called when you type `ip link set eth0 down` in your shell. Everything set up by `open()`
+    /// is released here, so the interface can be brought up again later.
+    fn stop(dev: &net::Device, data: &NetDevicePrvData) -> Result {
+        pr_info!("Rust for linux e1000 driver demo (net device stop)\n");
+
+        // A pending reset is not cancelled, it needs the rtnl lock held here. It does nothing once the interface is
+        // down, and the netdev is kept alive until it has run.
+        Self::e1000_down(dev, data)
+    }
+
+
+    /// `ip link set eth0 mtu N`, the range is checked by the kernel against the `max_mtu` set in `probe()`. The rx ring
+    /// is reallocated with buffers of the new size if the interface is up, the MTU is kept if that fails.
+    fn change_mtu(dev: &net::Device, data: &NetDevicePrvData, new_mtu: i32) -> Result {
+        Self::e1000_reinit(dev, data, data.tx_ring_count.load(Ordering::Relaxed), data.rx_ring_count.load(Ordering::Relaxed), new_mtu as u32)
+    }
+
+
+    /// Called by the kernel when the tx queue has been stopped for longer than `dev->watchdog_timeo`, corresponding
+    /// to the C version `e1000_tx_timeout()`. The ring state is dumped here, while it's still the one that hung, then
+    /// the adapter is reset by `e1000_reset_task()`.
+    fn tx_timeout(dev: &net::Device, data: &NetDevicePrvData, _txqueue: u32) {
+        if let Some(tx_ring) = data.tx_ring.lock_irqdisable().as_mut() {
+            Self::e1000_dump_tx_ring(data, tx_ring);
+        }
+
+        if dev.netif_running() {
+            Self::e1000_schedule_reset(dev, data);
+        }
+    }
+
+    /// `ip link set eth0 address XX:XX:XX:XX:XX:XX`, corresponding to the C version `e1000_set_mac()`. RA[0] is
+    /// reprogrammed at once, so it works while the interface is up. `dev->perm_addr` is not changed, `ethtool -P`
+    /// still shows the address in the EEPROM.
+    fn set_mac_address(dev: &net::Device, data: &NetDevicePrvData, sa: &bindings::sockaddr) -> Result {
+        let mut addr = [0u8; 6];
+        for (dst, src) in addr.iter_mut().zip(sa.sa_data.iter()) {
+            *dst = *src as u8;
+        }
+        if !is_valid_ether_addr(&addr) {
+            return Err(kernel::error::code::EADDRNOTAVAIL);
+        }
+
+        let mut mac_addr = data.mac_addr.lock_irqdisable();
+        data.e1000_hw_ops.e1000_rar_set(&addr, 0)?;
+        *mac_addr = addr;
+        drop(mac_addr);
+
+        dev.eth_hw_addr_set(&addr);
+        Ok(())
+    }
+
+    /// Called when a VLAN device is added on top of us, only the 802.1Q tags are filtered.
+    fn vlan_rx_add_vid(_dev: &net::Device, data: &NetDevicePrvData, _proto: u16, vid: u16) -> Result {
+        Self::e1000_vfta_set(data, vid, true)
+    }
+
+    fn vlan_rx_kill_vid(_dev: &net::Device, data: &NetDevicePrvData, _proto: u16, vid: u16) -> Result {
+        Self::e1000_vfta_set(data, vid, false)
+    }
+
+    /// Called when the address lists or the flags of the interface change, e.g. `ip maddr add` or `ip link set eth0
+    /// promisc on`. It runs in atomic context.
+    fn set_rx_mode(dev: &net::Device, data: &NetDevicePrvData) {
+        if let Err(e) = Self::e1000_set_rx_mode(dev, data) {
+            pr_err!("Failed to set the rx mode: {:?}\n", e);
+        }
+    }
+
+    /// Corresponding to the C version `e1000_fix_features()`. The VLAN tag insertion can't be enabled without the
+    /// stripping, `ethtool -K eth0 rxvlan on|off` toggles both of them.
+    fn fix_features(_dev: &net::Device, features: u64) -> u64 {
+        if features & NETIF_F_HW_VLAN_CTAG_RX != 0 {
+            features | NETIF_F_HW_VLAN_CTAG_TX
+        } else {
+            features & !NETIF_F_HW_VLAN_CTAG_TX
+        }
+    }
+
+    /// `ethtool -K eth0 ...`, corresponding to the C version `e1000_set_features()`. Only the features that need the
+    /// hardware reprogrammed are handled here, the kernel updates `dev->features` once this returns.
+    fn set_features(dev: &net::Device, data: &NetDevicePrvData, features: u64) -> Result {
+        let changed = data.features.load(Ordering::Relaxed) ^ features;
+
+        if changed & NETIF_F_RXCSUM != 0 {
+            data.e1000_hw_ops.e1000_set_rx_csum(features & NETIF_F_RXCSUM != 0)?;
+        }
+
+        if changed & NETIF_F_HW_VLAN_CTAG_RX != 0 {
+            data.e1000_hw_ops.e1000_vlan_mode(features & NETIF_F_HW_VLAN_CTAG_RX != 0)?;
+        }
+
+        if changed & NETIF_F_HW_VLAN_CTAG_FILTER != 0 {
+            Self::e1000_update_vlan_filter(dev, data, features)?;
+        }
+
+        // The rx path also picks the checksum mode from them, see `e1000_rx_csum_complete()`.
+        data.features.store(features, Ordering::Relaxed);
+        Ok(())
+    }
+
+
+    fn start_xmit(skb: &net::SkBuff, dev: &net::Device, data: &NetDevicePrvData) -> net::NetdevTx {
+        let mut tx_ring = data.tx_ring.lock_irqdisable();
+        // The queue is disabled before the rings are released, but it may be woken up by the stack after a restart
+        // failed to set up new ones.
+        let tx_ring = match tx_ring.as_mut() {
+            Some(tx_ring) => tx_ring,
+            None => {
+                skb.napi_consume(0);
+                data.tx_dropped.fetch_add(1, Ordering::Relaxed);
+                return net::NetdevTx::Ok;
+            }
+        };
+
+        /* On PCI/PCI-X HW, if packet size is less than ETH_ZLEN,
+        * packets may get corrupted during padding by HW.
+        * To WA this issue, pad all small packets manually.
+        */
+        skb.put_padto(bindings::ETH_ZLEN);
+
+        // The queue is stopped below when a packet with the most page frags may not fit, but their buffers may be
+        // split into more descriptors than that.
+        if tx_ring.unused_count() < Self::e1000_tx_desc_count(skb) {
+            dev.netif_stop_queue();
+            Self::e1000_tx_doorbell(dev, data, tx_ring);
+            return net::NetdevTx::Busy;
+        }
+
+        let first = tx_ring.next_to_use;
+        let mut tx_flags = 0;
+        let mut hdr_len = 0;
+        if let Some(tci) = skb.vlan_tag() {
+            tx_flags |= E1000_TX_FLAGS_VLAN | (tci as u32) << E1000_TX_FLAGS_VLAN_SHIFT;
+        }
+        match Self::e1000_tso(tx_ring, skb) {
+            Ok(Some(len)) => {
+                tx_flags |= E1000_TX_FLAGS_TSO;
+                if skb.gso_type() & bindings::SKB_GSO_TCPV4 != 0 {
+                    tx_flags |= E1000_TX_FLAGS_IPV4;
+                }
+                hdr_len = len;
+            }
+            Ok(None) => match Self::e1000_tx_csum(tx_ring, skb) {
+                Ok(true) => tx_flags |= E1000_TX_FLAGS_CSUM,
+                Ok(false) => (),
+                Err(_) => return Self::e1000_drop_tx_skb(skb, dev, data, tx_ring),
+            },
+            Err(_) => return Self::e1000_drop_tx_skb(skb, dev, data, tx_ring),
+        }
+
+        let last = match Self::e1000_tx_map(data, tx_ring, skb, tx_flags) {
+            Ok(last) => last,
+            Err(_) => {
+                pr_err!("TX DMA map failed\n");
+                // Give back the context descriptor, if any.
+                tx_ring.buf.borrow_mut()[first] = None;
+                tx_ring.next_to_use = first;
+                return Self::e1000_drop_tx_skb(skb, dev, data, tx_ring);
+            }
+        };
+
+        // tell the kernel that we have pended some data to the hardware. Every segment but the first carries another
+        // copy of the headers, corresponding to the C version `e1000_tx_map()`.
+        let segs = core::cmp::max(skb.gso_segs(), 1);
+        let bytecount = ((segs - 1) * hdr_len) as u32 + skb.len();
+        dev.sent_queue(bytecount);
+
+        let tx_descs:&mut [TxDescEntry] = tx_ring.desc.as_desc_slice();
+        let mut buf = tx_ring.buf.borrow_mut();
+
+        tx_descs[last].cmd |= ((E1000_TXD_CMD_RS | E1000_TXD_CMD_EOP) >> 24) as u8;
+        let tx_buffer = buf[last].as_mut().unwrap();
+        tx_buffer.skb = Some(skb.into());
+        tx_buffer.segs = segs as u32;
+        tx_buffer.bytecount = bytecount;
+        buf[first].as_mut().unwrap().next_to_watch = Some(last);
+        drop(buf);
+
+        // The descriptors are written before the doorbell, `writel()` of the tail register orders them for the device.
+        tx_ring.next_to_use = (last + 1) % tx_descs.len();
+
+        // Stop the queue if the next packet may not fit, rather than returning `Busy` for it.
+        if tx_ring.unused_count() < E1000_TX_DESC_NEEDED {
+            dev.netif_stop_queue();
+        }
+
+        Self::e1000_tx_doorbell(dev, data, tx_ring);
+
+        net::NetdevTx::Ok
+    }
+
+
+
+    /// The counters are refreshed by the watchdog, so they may lag behind the hardware by up to
+    /// `E1000_WATCHDOG_INTERVAL_MS`, same as the C version.
+    fn get_stats64(_netdev: &net::Device, data: &NetDevicePrvData, stats: &mut net::RtnlLinkStats64) {
+        let hw_stats = data.hw_stats.lock_irqdisable();
+
+        stats.set_rx_packets(hw_stats.gprc);
+        stats.set_tx_packets(hw_stats.gptc);
+        stats.set_rx_bytes(hw_stats.gorc);
+        stats.set_tx_bytes(hw_stats.gotc);
+        stats.set_multicast(hw_stats.mprc);
+        stats.set_collisions(hw_stats.colc);
+
+        /* Rx Errors */
+        stats.set_rx_errors(hw_stats.rxerrc + hw_stats.crcerrs + hw_stats.algnerrc + hw_stats.rlec + hw_stats.cexterr);
+        stats.set_rx_length_errors(hw_stats.rlec);
+        stats.set_rx_crc_errors(hw_stats.crcerrs);
+        stats.set_rx_frame_errors(hw_stats.algnerrc);
+        stats.set_rx_missed_errors(hw_stats.mpc);
+
+        /* Tx Errors */
+        stats.set_tx_errors(hw_stats.ecol + hw_stats.latecol);
+        stats.set_tx_aborted_errors(hw_stats.ecol);
+        stats.set_tx_window_errors(hw_stats.latecol);
+        stats.set_tx_carrier_errors(hw_stats.tncrs);
+
+        stats.set_tx_dropped(data.tx_dropped.load(Ordering::Relaxed));
+        stats.set_rx_dropped(data.rx_dropped.load(Ordering::Relaxed));
+    }
+}
+
+// since the ownership limitation, We can't use NetDevicePrvData as C code, so we need to define a new type here. 
+struct IrqPrivateData {
+    e1000_hw_ops: Arc<E1000Ops>,
+    napi: Arc<net::Napi>,
+    irq_causes: Arc<AtomicU32>,
+}
+
+struct E1000InterruptHandler {}
+
+impl kernel::irq::Handler for E1000InterruptHandler {
+    type Data = Box<IrqPrivateData>;
+
+    fn handle_irq(data: &IrqPrivateData) -> kernel::irq::Return {
+        let pending_irqs = data.e1000_hw_ops.e1000_read_interrupt_state();
+
+        if pending_irqs == 0 {
+            return kernel::irq::Return::None
+        }
+
+        // Record the causes for the napi poll. ICR is cleared by the read above, so they would be lost otherwise.
+        data.irq_causes.fetch_or(pending_irqs, Ordering::Relaxed);
+
+        // Mask the interrupts until the napi poll is completed, corresponding to the C version `e1000_intr()`.
+        data.e1000_hw_ops.e1000_irq_disable();
+
+        data.napi.schedule();
+
+        kernel::irq::Return::Handled
+    }
+}
+
+
+/// Runs every `E1000_WATCHDOG_INTERVAL_MS` while the interface is up, corresponding to the C version
+/// `e1000_watchdog()`.
+unsafe extern "C" fn e1000_watchdog(work: *mut bindings::work_struct) {
+    // SAFETY: the work item is embedded in `NetDevicePrvData`, and it's cancelled in `e1000_down()` before the data is
+    // freed.
+    let data = unsafe{&*kernel::container_of!(work as *const DelayedWork, NetDevicePrvData, watchdog_task)};
+    // SAFETY: `netdev` is set in `open()` before the work item can be scheduled, and the netdev outlives the data.
+    let dev = unsafe{&*(data.netdev.load(Ordering::Relaxed) as *const net::Device)};
+
+    NetDevice::e1000_update_link(dev, data);
+    NetDevice::e1000_update_stats(data);
+
+    if NetDevice::e1000_check_tx_hang(dev, data) {
+        NetDevice::e1000_schedule_reset(dev, data);
+    } else {
+        // Get the rings cleaned even if an interrupt is lost, the rx buffers that couldn't be allocated are also
+        // retried by the napi poll.
+        if let Err(e) = data.e1000_hw_ops.e1000_trigger_rx_irq() {
+            pr_err!("Failed to trigger the rx interrupt: {:?}\n", e);
+        }
+    }
+
+    // Keep running even if a reset is scheduled, it may fail before the interface is restarted. `e1000_down()`
+    // cancels it.
+    data.watchdog_task.schedule_msecs(E1000_WATCHDOG_INTERVAL_MS);
+}
+
+/// Apply the profile picked by `net_dim()`, corresponding to the C version `e1000_update_itr()` and `e1000_set_itr()`.
+unsafe extern "C" fn e1000_rx_dim_work(work: *mut bindings::work_struct) {
+    // SAFETY: the work item is the one of `rx_dim`, it's cancelled in `e1000_down()` before the data is freed.
+    let dim = unsafe{Dim::from_work(work)};
+    // SAFETY: `rx_dim` is embedded in `NetDevicePrvData`.
+    let data = unsafe{&*kernel::container_of!(dim as *const Dim, NetDevicePrvData, rx_dim)};
+
+    // Checked under the lock, `set_coalesce()` may have turned it off and written the static values meanwhile.
+    let coalesce = data.coalesce.lock_irqdisable();
+    if coalesce.adaptive_rx {
+        if let Err(e) = NetDevice::e1000_set_rx_dim_moderation(data) {
+            pr_err!("Failed to set the rx moderation: {:?}\n", e);
+        }
+    }
+    drop(coalesce);
+
+    dim.start_measure();
+}
+
+/// Reset the adapter and rebuild the rings, corresponding to the C version `e1000_reset_task()`. New rings are set up
+/// first, then the queue and NAPI are stopped and the hardware is reset by `e1000_down()`, and `e1000_up()` programs
+/// the new rings into the hardware and checks the link again. If it fails, the interface is left down and the next
+/// reset or `stop()` starts from there.
+unsafe extern "C" fn e1000_reset_task(work: *mut bindings::work_struct) {
+    // SAFETY: the work item is embedded in `NetDevicePrvData`. The netdev, and the data with it, is kept alive by the
+    // reference taken in `e1000_schedule_reset()`, which is dropped at the end of this function.
+    let data = unsafe{&*kernel::container_of!(work as *const Work, NetDevicePrvData, reset_task)};
+    let dev_ref = match data.reset_netdev.lock_irqdisable().take() {
+        Some(dev_ref) => dev_ref,
+        None => return,
+    };
+    let dev = &*dev_ref;
+
+    // SAFETY: FFI call without preconditions, this is not called with the rtnl lock held.
+    unsafe{bindings::rtnl_lock()};
+
+    if dev.netif_running() {
+        pr_err!("Reset adapter\n");
+        data.tx_timeout_count.fetch_add(1, Ordering::Relaxed);
+        if let Err(e) = NetDevice::e1000_reinit(dev, data, data.tx_ring_count.load(Ordering::Relaxed), data.rx_ring_count.load(Ordering::Relaxed), dev.mtu_get()) {
+            pr_err!("Failed to reset the adapter: {:?}\n", e);
+        }
+    }
+
+    // SAFETY: the rtnl lock is taken above.
+    unsafe{bindings::rtnl_unlock()};
+}
+
+/// The PCI resources requested in `probe()`. They are given back through the same `pci::Device` API when this is
+/// dropped, corresponding to the tail of the C version `e1000_remove()`.
+struct PciResources {
+    pdev: pci::Device,
+    bars: i32,
+}
+
+impl Drop for PciResources {
+    fn drop(&mut self) {
+        self.pdev.release_selected_regions(self.bars);
+        self.pdev.disable_device();
+    }
+}
+
+/// the private data for the adapter
+struct E1000DrvPrvData {
+    // Must hold this registration, or the device will be removed. It's taken out in `remove()`.
+    netdev_reg: SpinLock<Option<net::Registration<NetDevice>>>,
+    e1000_hw_ops: Arc<E1000Ops>,
+    // Hold a reference so the napi instance is still alive when `free_netdev()` deletes it from the netdev.
+    napi: Arc<net::Napi>,
+    // Fields are dropped in declaration order, this must be the last one so the BAR0 mapping held by `e1000_hw_ops`
+    // is released before the regions.
+    _pci_res: PciResources,
+}
+
+impl driver::DeviceRemoval for E1000DrvPrvData {
+    fn device_remove(&self) {
+        pr_info!("Rust for linux e1000 driver demo (device_remove)\n");
+    }
+}
+
+struct NapiHandler{}
+
+impl net::NapiPoller for NapiHandler {
+    type Data = Box<NetDevicePrvData>;
+
+    fn poll(
+        _napi: &net::Napi,
+        budget: i32,
+        dev: &net::Device,
+        data: &NetDevicePrvData,
+    ) -> i32 {
+        // The carrier can't be changed in the irq handler since it doesn't have the netdev, so it's done here.
+        // The rx and tx rings are cleaned by checking the DD bits, the causes are not needed for them. This way a
+        // poll rescheduled because of an exhausted budget continues where the last one stopped.
+        let irq_causes = data.irq_causes.swap(0, Ordering::Relaxed);
+        if irq_causes & (E1000_ICR_LSC | E1000_ICR_RXSEQ) != 0 {
+            NetDevice::e1000_update_link(dev, data);
+        }
+
+        NetDevice::e1000_recycle_tx_queue(dev, data);
+        let work_done = NetDevice::e1000_clean_rx_irq(dev, data, budget);
+
+        if data.coalesce.lock_irqdisable().adaptive_rx {
+            data.rx_dim.update(data.total_rx_packets.load(Ordering::Relaxed), data.total_rx_bytes.load(Ordering::Relaxed));
+        }
+
+        // If the budget is used up, don't complete the poll, the kernel will call us again soon. The interrupts are
+        // kept masked until then, corresponding to the C version `e1000_clean()`.
+        if work_done < budget && data.napi.complete_done(work_done) {
+            data.e1000_hw_ops.e1000_irq_enable();
+        }
+        work_done
+    }
+}
+
+/// Names of the driver counters shown by `ethtool -S`, in the order of `get_ethtool_stats()`.
+const E1000_GSTRINGS_STATS: [&str; 4] = [
+    "rx_csum_offload_good",
+    "rx_csum_offload_errors",
+    "rx_driver_dropped",
+    "tx_timeout_count",
+];
+
+/// Report the raw packet checksum as `CHECKSUM_COMPLETE`, see `NetDevice::e1000_rx_csum_complete()`.
+const E1000_PRIV_FLAG_RX_CSUM_COMPLETE: u32 = 1 << 0;
+
+/// Names of the private flags shown by `ethtool --show-priv-flags`, in the order of their bits.
+const E1000_GSTRINGS_PRIV_FLAGS: [&str; 1] = [
+    "rx-csum-complete",
+];
+
+struct E1000EthtoolOps {}
+
+#[vtable]
+impl net::EthToolOps for E1000EthtoolOps {
+    type Data = Box<NetDevicePrvData>;
+
+    const SUPPORTED_COALESCE_PARAMS: u32 = ETHTOOL_COALESCE_RX_USECS | ETHTOOL_COALESCE_RX_USECS_HIGH |
+        ETHTOOL_COALESCE_TX_USECS | ETHTOOL_COALESCE_TX_USECS_HIGH | ETHTOOL_COALESCE_USE_ADAPTIVE_RX;
+
+    fn get_sset_count(_dev: &net::Device, _data: &NetDevicePrvData, sset: u32) -> Result<i32> {
+        match sset {
+            bindings::ethtool_stringset_ETH_SS_STATS => Ok(E1000_GSTRINGS_STATS.len() as i32),
+            bindings::ethtool_stringset_ETH_SS_PRIV_FLAGS => Ok(E1000_GSTRINGS_PRIV_FLAGS.len() as i32),
+            _ => Err(kernel::error::code::EOPNOTSUPP),
+        }
+    }
+
+    fn get_strings(_dev: &net::Device, _data: &NetDevicePrvData, stringset: u32, buf: &mut [u8]) {
+        let names: &[&str] = match stringset {
+            bindings::ethtool_stringset_ETH_SS_STATS => &E1000_GSTRINGS_STATS,
+            bindings::ethtool_stringset_ETH_SS_PRIV_FLAGS => &E1000_GSTRINGS_PRIV_FLAGS,
+            _ => return,
+        };
+        for (name, dst) in names.iter().zip(buf.chunks_mut(bindings::ETH_GSTRING_LEN as usize)) {
+            dst.fill(0);
+            dst[..name.len()].copy_from_slice(name.as_bytes());
+        }
+    }
+
+    /// `ethtool -S eth0`
+    fn get_ethtool_stats(_dev: &net::Device, data: &NetDevicePrvData, stats: &mut [u64]) {
+        let values = [
+            data.rx_csum_good.load(Ordering::Relaxed),
+            data.rx_csum_err.load(Ordering::Relaxed),
+            data.rx_dropped.load(Ordering::Relaxed),
+            data.tx_timeout_count.load(Ordering::Relaxed),
+        ];
+        for (dst, value) in stats.iter_mut().zip(values) {
+            *dst = value;
+        }
+    }
+
+    /// `ethtool --show-priv-flags eth0`
+    fn get_priv_flags(_dev: &net::Device, data: &NetDevicePrvData) -> u32 {
+        data.priv_flags.load(Ordering::Relaxed)
+    }
+
+    /// `ethtool --set-priv-flags eth0 rx-csum-complete on|off`, it takes effect from the next napi poll.
+    fn set_priv_flags(_dev: &net::Device, data: &NetDevicePrvData, flags: u32) -> Result {
+        if flags & !E1000_PRIV_FLAG_RX_CSUM_COMPLETE != 0 {
+            return Err(kernel::error::code::EINVAL);
+        }
+        data.priv_flags.store(flags, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// `ethtool -g eth0`
+    fn get_ringparam(_dev: &net::Device, data: &NetDevicePrvData, ring: &mut net::EthtoolRingparam) {
+        ring.set_rx_max_pending(E1000_MAX_RXD as u32);
+        ring.set_tx_max_pending(E1000_MAX_TXD as u32);
+        ring.set_rx_pending(data.rx_ring_count.load(Ordering::Relaxed) as u32);
+        ring.set_tx_pending(data.tx_ring_count.load(Ordering::Relaxed) as u32);
+    }
+
+    /// `ethtool -G eth0 rx N tx N`, the rings are reallocated and the interface is restarted if it's up.
+    /// Corresponding to the C version `e1000_set_ringparam()`.
+    fn set_ringparam(dev: &net::Device, data: &NetDevicePrvData, ring: &net::EthtoolRingparam) -> Result {
+        if ring.rx_mini_pending() != 0 || ring.rx_jumbo_pending() != 0 {
+            return Err(kernel::error::code::EINVAL);
+        }
+
+        // Same as the C version, out of range values are clamped and rounded up to the required multiple.
+        let align = |n: u32, min: usize, max: usize| -> usize {
+            let n = (n as usize).clamp(min, max);
+            (n + E1000_REQ_DESCRIPTOR_MULTIPLE - 1) / E1000_REQ_DESCRIPTOR_MULTIPLE * E1000_REQ_DESCRIPTOR_MULTIPLE
+        };
+        let rx_count = align(ring.rx_pending(), E1000_MIN_RXD, E1000_MAX_RXD);
+        let tx_count = align(ring.tx_pending(), E1000_MIN_TXD, E1000_MAX_TXD);
+
+        if data.rx_ring_count.load(Ordering::Relaxed) == rx_count && data.tx_ring_count.load(Ordering::Relaxed) == tx_count {
+            return Ok(());
+        }
+
+        // The interface keeps running with the old rings if the new ones can't be allocated.
+        NetDevice::e1000_reinit(dev, data, tx_count, rx_count, dev.mtu_get())
+    }
+
+    /// `ethtool -c eth0`
+    fn get_coalesce(_dev: &net::Device, data: &NetDevicePrvData, coal: &mut net::EthtoolCoalesce) {
+        let coalesce = data.coalesce.lock_irqdisable();
+        coal.set_rx_coalesce_usecs(coalesce.rx_usecs);
+        coal.set_rx_coalesce_usecs_high(coalesce.rx_usecs_high);
+        coal.set_tx_coalesce_usecs(coalesce.tx_usecs);
+        coal.set_tx_coalesce_usecs_high(coalesce.tx_usecs_high);
+        coal.set_use_adaptive_rx_coalesce(coalesce.adaptive_rx);
+    }
+
+    /// `ethtool -C eth0 rx-usecs N rx-usecs-high N tx-usecs N tx-usecs-high N`, the RDTR, RADV, TIDV and TADV timers.
+    /// The registers are written at once, even if the interface is up. ethtool has no field for an interrupt rate, so
+    /// the ITR cap in interrupts per second is kept from the `interrupt_throttle_rate` module parameter, same as the C
+    /// version. With `adaptive-rx on`, ITR and RDTR are picked by `net_dim()` instead.
+    fn set_coalesce(_dev: &net::Device, data: &NetDevicePrvData, coal: &net::EthtoolCoalesce) -> Result {
+        let mut coalesce = data.coalesce.lock_irqdisable();
+        let new = E1000Coalesce {
+            rx_usecs: coal.rx_coalesce_usecs(),
+            rx_usecs_high: coal.rx_coalesce_usecs_high(),
+            tx_usecs: coal.tx_coalesce_usecs(),
+            tx_usecs_high: coal.tx_coalesce_usecs_high(),
+            itr: coalesce.itr,
+            adaptive_rx: coal.use_adaptive_rx_coalesce(),
+        };
+
+        data.e1000_hw_ops.e1000_set_coalesce(&new)?;
+        if new.adaptive_rx {
+            NetDevice::e1000_set_rx_dim_moderation(data)?;
+        }
+        *coalesce = new;
+        data.txd_cmd.store(NetDevice::e1000_txd_cmd(&new), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+struct E1000Drv {}
+
+impl E1000Drv {
+    /// Validate the EEPROM and read the MAC address stored in it.
+    fn e1000_read_perm_addr(e1000_hw_ops: &E1000Ops) -> Result<[u8; 6]> {
+        e1000_hw_ops.e1000_validate_eeprom_checksum()?;
+        let mac_addr = e1000_hw_ops.e1000_read_mac_addr()?;
+        if !is_valid_ether_addr(&mac_addr) {
+            return Err(kernel::error::code::EINVAL);
+        }
+        Ok(mac_addr)
+    }
+}
+
+/// The ITR cap of a new adapter from the `interrupt_throttle_rate` module parameter, corresponding to the C version
+/// `e1000_validate_option()` for `InterruptThrottleRate`. The special values 1 to 4 of the C version, which pick the
+/// dynamic modes, are not supported, `ethtool -C eth0 adaptive-rx on` does that.
+fn e1000_itr_param() -> u32 {
+    let itr = *interrupt_throttle_rate.read();
+    if itr != 0 && !(E1000_MIN_INTS_PER_SEC..=E1000_MAX_INTS_PER_SEC).contains(&itr) {
+        pr_warn!("Invalid Interrupt Throttling Rate {}, using default of {}\n", itr, E1000_DEFAULT_ITR);
+        return E1000_DEFAULT_ITR;
+    }
+    itr
+}
+
+/// Same as the C version `is_valid_ether_addr()`, the address must be neither multicast nor all zeros.
+fn is_valid_ether_addr(addr: &[u8; 6]) -> bool {
+    addr[0] & 0x01 == 0 && addr.iter().any(|b| *b != 0)
+}
+
+/// Same as the C version `eth_random_addr()`, generate a random unicast and locally administered address.
+fn random_ether_addr() -> Result<[u8; 6]> {
+    let mut addr = [0u8; 6];
+    kernel::random::getrandom(&mut addr)?;
+    addr[0] &= 0xfe;	/* clear multicast bit */
+    addr[0] |= 0x02;	/* set local assignment bit (IEEE802) */
+    Ok(addr)
+}
+
+
+
+impl pci::Driver for E1000Drv {
+
+    // The Box type has implemented PointerWrapper trait.
+    type Data = Box<E1000DrvPrvData>;
+
+    kernel::define_pci_id_table! {(), [
+        (pci::DeviceId::new(E1000_VENDER_ID, E1000_DEVICE_ID), None),
+    ]}
+
+
+    fn probe(dev: &mut pci::Device, id: core::option::Option<&Self::IdInfo>) -> Result<Self::Data> {
+        pr_info!("Rust for linux e1000 driver demo (probe): {:?}\n", id);
+
+        // Note: only support QEMU's 82540EM chip now.
+        
+        // this works like a filter, the PCI device may have up to 6 bars, those bars have different types,
+        // some of them are mmio, others are io-port based. The params to the following function is a 
+        // filter condition, and the return value is a mask indicating which of those bars are selected.
+        let bars = dev.select_bars((bindings::IORESOURCE_MEM | bindings::IORESOURCE_IO) as u64);
+
+        // the underlying will call `pci_enable_device()`. the R4L framework doesn't support `pci_enable_device_memory()` now.
+        dev.enable_device()?;
+
+        // ask the os to reserve the physical memory region of the selected bars. The device is disabled again if that
+        // fails, same as the C label `err_pci_reg`.
+        if let Err(e) = dev.request_selected_regions(bars, c_str!("e1000 reserved memory")) {
+            dev.disable_device();
+            return Err(e);
+        }
+
+        // From now on, the regions are released and the device is disabled if anything below fails.
+        let pci_res = PciResources {
+            // SAFETY: the PCI core keeps the device alive until the driver is unbound from it, `remove()` returns
+            // before that.
+            pdev: unsafe{pci::Device::from_ptr(kernel::container_of!(dev.raw_device(), bindings::pci_dev, dev) as *mut bindings::pci_dev)},
+            bars,
+        };
+
+        // set device to master mode.
+        dev.set_master();
+
+        // get resource(memory range) provided by BAR0
+        let mem_res = dev.iter_resource().next().ok_or(kernel::error::code::EIO)?;
+        let io_res = dev.iter_resource().skip(1).find(|r:&Resource|r.check_flags(bindings::IORESOURCE_IO)).ok_or(kernel::error::code::EIO)?;
+
+        // TODO pci_save_state(pdev); not supported by crate now, only have raw C bindings.
+
+        // alloc new ethernet device, this line represent the `alloc_etherdev()` and `SET_NETDEV_DEV()` in C version.
+        let mut netdev_reg = net::Registration::<NetDevice>::try_new(dev)?;
+        let netdev = netdev_reg.dev_get();
+
+        // map device registers' hardware address to logical address so the kernel driver can access it.
+        let mem_addr = Arc::try_new(dev.map_resource(&mem_res, mem_res.len())?)?;
+
+        // get the io-port based address
+        let io_addr = Arc::try_new(pci::IoPort::try_new(&io_res)?)?;
+
+
+
+        // TODO implement C version `e1000_init_hw_struct()`
+
+        // only pci-x need 64-bit, to simplify code, hardcode 32-bit for now.
+        dma::set_coherent_mask(dev, 0xFFFFFFFF)?;
+
+        netdev.set_ethtool_ops::<E1000EthtoolOps>();
+
+        // The secondary unicast addresses are filtered by RA[1..], see `e1000_set_rx_mode()`.
+        netdev.add_priv_flags(bindings::netdev_priv_flags_IFF_UNICAST_FLT);
+
+        netdev.set_hw_features(E1000_FEATURES);
+        netdev.set_features(E1000_FEATURES);
+
+        // Enable napi, the R4L will call `netif_napi_add_weight()`, the origin C version calls `netif_napi_add`
+        let napi = net::NapiAdapter::<NapiHandler>::add_weight(&netdev, 64)?;
+
+
+        // TODO implement C version `e1000_sw_init()`
+
+        // TODO a lot of feature flags are assigned here in the C code, skip them for now.
+        let e1000_hw_ops = E1000Ops {
+            mem_addr: Arc::clone(&mem_addr),
+            io_addr: Arc::clone(&io_addr),
+        };
+        e1000_hw_ops.e1000_reset_hw()?;
+
+
+        // Read the permanent MAC address out of the EEPROM. The C version refuses to probe if the EEPROM is broken,
+        // here we fall back to a random address so the adapter is still usable.
+        let (mac_addr, addr_assign_type) = match Self::e1000_read_perm_addr(&e1000_hw_ops) {
+            Ok(mac_addr) => (mac_addr, bindings::NET_ADDR_PERM),
+            Err(e) => {
+                pr_warn!("Invalid EEPROM ({:?}), using a random MAC address\n", e);
+                (random_ether_addr()?, bindings::NET_ADDR_RANDOM)
+            }
+        };
+        netdev.eth_hw_addr_set(&mac_addr);
+        // Tell userspace whether the address is the permanent one, same as the C version `eth_hw_addr_random()` does
+        // for the random one. The R4L crate doesn't support setting it now.
+        // SAFETY: the netdev is not registered yet, nothing else uses it.
+        unsafe{(*(&*netdev as *const net::Device as *mut bindings::net_device)).addr_assign_type = addr_assign_type as u8};
+
+        // The 82540 takes jumbo frames up to `MAX_JUMBO_FRAME_SIZE`, the FCS and the ethernet header are not part of
+        // the MTU.
+        netdev.set_max_mtu((MAX_JUMBO_FRAME_SIZE - (ENET_HEADER_SIZE + ETHERNET_FCS_SIZE)) as u32);
+
+        // TODO: Wake on LAN is not supported now.
+
+        let irq = dev.irq();
+
+        let common_dev = device::Device::from_dev(dev);
+
+        netdev.netif_carrier_off();
+
+
+        // SAFETY: `spinlock_init` is called below.
+        let mut tx_ring = unsafe{SpinLock::new(None)};
+        let mut rx_ring = unsafe{SpinLock::new(None)};
+        let mut link_status = unsafe{SpinLock::new(E1000LinkStatus::default())};
+        let mut hw_stats = unsafe{SpinLock::new(E1000HwStats::default())};
+        let mut active_vlans = unsafe{SpinLock::new([0; E1000_VLAN_FILTER_TBL_SIZE])};
+        let mut mac_addr = unsafe{SpinLock::new(mac_addr)};
+        let mut coalesce = unsafe{SpinLock::new(E1000Coalesce{itr: e1000_itr_param(), ..Default::default()})};
+        let mut reset_netdev = unsafe{SpinLock::new(None)};
+        // SAFETY: We don't move `tx_ring`, `rx_ring`, `link_status`, `hw_stats`, `active_vlans`, `mac_addr`,
+        // `coalesce` and `reset_netdev`.
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut tx_ring)}, "tx_ring");
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut rx_ring)}, "rx_ring");
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut link_status)}, "link_status");
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut hw_stats)}, "hw_stats");
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut active_vlans)}, "active_vlans");
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut mac_addr)}, "mac_addr");
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut coalesce)}, "coalesce");
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut reset_netdev)}, "reset_netdev");
+
+
+        let e1000_hw_ops = Arc::try_new(e1000_hw_ops)?;
+        let napi: Arc<net::Napi> = napi.into();
+
+        let prv_data = Box::try_new(
+            NetDevicePrvData {
+                dev: Arc::try_new(common_dev)?,
+                e1000_hw_ops: Arc::clone(&e1000_hw_ops),
+                napi: Arc::clone(&napi),
+                tx_ring,
+                rx_ring,
+                up: AtomicBool::new(false),
+                tx_ring_count: AtomicUsize::new(E1000_DEFAULT_TXD),
+                rx_ring_count: AtomicUsize::new(E1000_DEFAULT_RXD),
+                irq,
+                mac_addr,
+                irq_handler: AtomicPtr::new(core::ptr::null_mut()),
+                irq_causes: Arc::try_new(AtomicU32::new(0))?,
+                link_status,
+                hw_stats,
+                tx_dropped: AtomicU64::new(0),
+                rx_dropped: AtomicU64::new(0),
+                rx_csum_good: AtomicU64::new(0),
+                rx_csum_err: AtomicU64::new(0),
+                features: AtomicU64::new(E1000_FEATURES),
+                priv_flags: AtomicU32::new(0),
+                active_vlans,
+                coalesce,
+                txd_cmd: AtomicU32::new(NetDevice::e1000_txd_cmd(&E1000Coalesce::default())),
+                rx_dim: Dim::new(),
+                total_rx_packets: AtomicU64::new(0),
+                total_rx_bytes: AtomicU64::new(0),
+                watchdog_task: DelayedWork::new(),
+                netdev: AtomicPtr::new(core::ptr::null_mut()),
+                reset_task: Work::new(),
+                reset_netdev,
+                tx_timeout_count: AtomicU64::new(0),
+            }
+        )?;
+        // SAFETY: the work item is new, and the data is not moved out of the box while the netdev lives. It's
+        // initialized only once, a reset may still be pending when the interface is opened again.
+        unsafe{prv_data.reset_task.init(e1000_reset_task)};
+        netdev_reg.register(prv_data)?;
+
+        
+
+        // SAFETY: `spinlock_init` is called below.
+        let mut netdev_reg = unsafe{SpinLock::new(Some(netdev_reg))};
+        // SAFETY: We don't move `netdev_reg`.
+        kernel::spinlock_init!(unsafe{Pin::new_unchecked(&mut netdev_reg)}, "netdev_reg");
+
+        Ok(Box::try_new(
+            E1000DrvPrvData{
+                netdev_reg,
+                e1000_hw_ops,
+                napi,
+                _pci_res: pci_res,
+            }
+        )?)
+    }
+
+    /// Corresponding to the C version `e1000_remove()`. The netdev is unregistered and the hardware is reset here,
+    /// then the BAR0 mapping and the PCI regions are released in this order when `data` is dropped.
+    fn remove(data: &Self::Data) {
+        pr_info!("Rust for linux e1000 driver demo (remove)\n");
+
+        // Take the registration out of the lock first, unregistering the netdev may sleep.
+        let netdev_reg = data.netdev_reg.lock().take();
+
+        // Dropping the registration unregisters the netdev, which calls `stop()` if the interface is still up, so the
+        // irq, NAPI and rings are released there. Then `free_netdev()` deletes the NAPI instance.
+        drop(netdev_reg);
+
+        // Leave the MAC in a quiet state, no more DMA or interrupts after this.
+        if let Err(e) = data.e1000_hw_ops.e1000_reset_hw() {
+            pr_err!("Failed to reset the hardware on removal: {:?}\n", e);
+        }
+    }
+}
+struct E1000KernelMod {
+    _dev: Pin<Box<driver::Registration::<pci::Adapter<E1000Drv>>>>,
+}
+
+impl kernel::Module for E1000KernelMod {
+    fn init(name: &'static CStr, module: &'static ThisModule) -> Result<Self> {
+        pr_info!("Rust for linux e1000 driver demo (init)\n");
+        let d = driver::Registration::<pci::Adapter<E1000Drv>>::new_pinned(name, module)?;
+
+        // we need to store `d` into the module struct, otherwise it will be dropped, which 
+        // means the driver will be removed.
+        Ok(E1000KernelMod {_dev: d})
+    }
+}
+
+impl Drop for E1000KernelMod {
+    fn drop(&mut self) {
+        // `_dev` is dropped after this, which unregisters the pci driver and calls `remove()` for every adapter.
+        pr_info!("Rust for linux e1000 driver demo (exit)\n");
+    }
+}
@@ -1,4 +1,6 @@
 use kernel::prelude::*;
+use kernel::bindings;
+use kernel::error::code::{EINVAL, ETIMEDOUT};
 use kernel::pci::{MappedResource, IoPort};
 use kernel::delay::coarse_sleep;
 use kernel::sync::Arc;
@@ -6,9 +8,89 @@ use kernel::sync::Arc;
 use core::time::Duration;
 
 use crate::ring_buf::{RxRingBuf, TxRingBuf};
+use crate::hw_defs::{RxDescEntry, TxDescEntry};
 
 use crate::consts::*;
 
+/// Busy wait for a short while. The EEPROM interface needs microsecond level delays, which `coarse_sleep` can't give.
+fn udelay(usecs: u64) {
+    // SAFETY: FFI call without pointer arguments.
+    unsafe{bindings::__udelay(usecs as core::ffi::c_ulong)};
+}
+
+/// The link state reported by the STATUS register.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct E1000LinkStatus {
+    pub(crate) up: bool,
+    /// in Mbps
+    pub(crate) speed: u32,
+    pub(crate) full_duplex: bool,
+}
+
+/// The hardware statistics, accumulated from the clear-on-read registers. Correspond to C version
+/// `struct e1000_hw_stats`, only the counters used by the driver are kept.
+#[derive(Default)]
+pub(crate) struct E1000HwStats {
+    pub(crate) crcerrs: u64,
+    pub(crate) algnerrc: u64,
+    pub(crate) symerrs: u64,
+    pub(crate) rxerrc: u64,
+    pub(crate) mpc: u64,
+    pub(crate) scc: u64,
+    pub(crate) ecol: u64,
+    pub(crate) mcc: u64,
+    pub(crate) latecol: u64,
+    pub(crate) colc: u64,
+    pub(crate) dc: u64,
+    pub(crate) tncrs: u64,
+    pub(crate) cexterr: u64,
+    pub(crate) rlec: u64,
+    pub(crate) gprc: u64,
+    pub(crate) bprc: u64,
+    pub(crate) mprc: u64,
+    pub(crate) gptc: u64,
+    pub(crate) gorc: u64,
+    pub(crate) gotc: u64,
+    pub(crate) rnbc: u64,
+    pub(crate) ruc: u64,
+    pub(crate) roc: u64,
+    pub(crate) tpr: u64,
+    pub(crate) tpt: u64,
+    pub(crate) mptc: u64,
+    pub(crate) bptc: u64,
+}
+
+/// The interrupt moderation settings of `ethtool -C`. The timers are in microseconds, 0 turns them off.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct E1000Coalesce {
+    /// RDTR, the rx interrupt is delayed until no packet is received for this long.
+    pub(crate) rx_usecs: u32,
+    /// RADV, the most the rx interrupt is delayed by RDTR.
+    pub(crate) rx_usecs_high: u32,
+    /// TIDV, same as RDTR for the tx descriptors with `E1000_TXD_CMD_IDE`.
+    pub(crate) tx_usecs: u32,
+    /// TADV, same as RADV for the tx descriptors with `E1000_TXD_CMD_IDE`.
+    pub(crate) tx_usecs_high: u32,
+    /// ITR, the most interrupts per second, same as `adapter->itr` of the C version. 0 turns it off. It's set by the
+    /// `interrupt_throttle_rate` module parameter when the adapter is probed.
+    pub(crate) itr: u32,
+    /// ITR and RDTR are picked by `net_dim()` instead, the static values above are used when it's turned off.
+    pub(crate) adaptive_rx: bool,
+}
+
+impl Default for E1000Coalesce {
+    fn default() -> Self {
+        Self {
+            rx_usecs: E1000_DEFAULT_RDTR_USECS,
+            rx_usecs_high: E1000_DEFAULT_RADV_USECS,
+            tx_usecs: E1000_DEFAULT_TIDV_USECS,
+            tx_usecs_high: E1000_DEFAULT_TADV_USECS,
+            itr: E1000_DEFAULT_ITR,
+            adaptive_rx: false,
+        }
+    }
+}
+
 pub(crate) struct E1000Ops {
     pub(crate) mem_addr: Arc<MappedResource>,
     pub(crate) io_addr: Arc<IoPort>,
@@ -63,6 +145,35 @@ impl E1000Ops {
         Ok(())
     }
 
+    /// Stop the receive and transmit units, correspond to the register part of C version `e1000_down`.
+    pub(crate) fn e1000_disable_rx_tx(&self) -> Result {
+        /* disable receives in the hardware */
+        let rctl = self.mem_addr.readl(E1000_RCTL)?;
+        self.mem_addr.writel(rctl & !E1000_RCTL_EN, E1000_RCTL)?;
+
+        /* disable transmits in the hardware */
+        let tctl = self.mem_addr.readl(E1000_TCTL)?;
+        self.mem_addr.writel(tctl & !E1000_TCTL_EN, E1000_TCTL)?;
+
+        /* flush both disables and wait for them to finish */
+        self.e1000_write_flush();
+        coarse_sleep(Duration::from_millis(10));
+
+        Ok(())
+    }
+
+    /// Unmask the interrupts used by the driver, correspond to C version `e1000_irq_enable`.
+    pub(crate) fn e1000_irq_enable(&self) {
+        self.mem_addr.writel(IMS_ENABLE_MASK, E1000_IMS).unwrap();
+        self.e1000_write_flush();
+    }
+
+    /// Mask all the interrupts, correspond to C version `e1000_irq_disable`.
+    pub(crate) fn e1000_irq_disable(&self) {
+        self.mem_addr.writel(0xffffffff, E1000_IMC).unwrap();
+        self.e1000_write_flush();
+    }
+
     fn e1000_write_flush(&self){
         // This read shouldn't fail 
         self.mem_addr.readl(E1000_STATUS).unwrap();
@@ -74,15 +185,247 @@ impl E1000Ops {
         Ok(())
     }
 
-    pub(crate) fn e1000_configure(&self, rx_ring: &RxRingBuf, tx_ring: &TxRingBuf) -> Result {
-        self.e1000_configure_rx(rx_ring)?;
+    /// Read `data.len()` words from the EEPROM starting at word `offset`, correspond to C version `e1000_read_eeprom`.
+    /// The EERD register is tried first, and the EECD bit-bang interface is used if it never reports done.
+    pub(crate) fn e1000_read_eeprom(&self, offset: u16, data: &mut [u16]) -> Result {
+        if self.e1000_read_eeprom_eerd(offset, data).is_ok() {
+            return Ok(());
+        }
+        self.e1000_read_eeprom_microwire(offset, data)
+    }
+
+    /// Check that all the words up to and including the checksum word sum to `EEPROM_SUM`, correspond to C version
+    /// `e1000_validate_eeprom_checksum`.
+    pub(crate) fn e1000_validate_eeprom_checksum(&self) -> Result {
+        let mut words = [0u16; EEPROM_CHECKSUM_REG as usize + 1];
+        self.e1000_read_eeprom(0, &mut words)?;
+
+        let checksum = words.iter().fold(0u16, |sum, w| sum.wrapping_add(*w));
+        if checksum != EEPROM_SUM {
+            return Err(EINVAL);
+        }
+        Ok(())
+    }
+
+    /// Read the permanent MAC address stored in EEPROM words 0-2, correspond to C version `e1000_read_mac_addr`.
+    pub(crate) fn e1000_read_mac_addr(&self) -> Result<[u8; 6]> {
+        let mut words = [0u16; 3];
+        self.e1000_read_eeprom(0, &mut words)?;
+
+        let mut mac_addr = [0u8; 6];
+        for (i, w) in words.iter().enumerate() {
+            mac_addr[i * 2] = *w as u8;
+            mac_addr[i * 2 + 1] = (*w >> 8) as u8;
+        }
+        Ok(mac_addr)
+    }
+
+    fn e1000_read_eeprom_eerd(&self, offset: u16, data: &mut [u16]) -> Result {
+        for (i, word) in data.iter_mut().enumerate() {
+            let eerd = ((offset as u32 + i as u32) << E1000_EEPROM_RW_ADDR_SHIFT) | E1000_EEPROM_RW_REG_START;
+            self.mem_addr.writel(eerd, E1000_EERD)?;
+            self.e1000_poll_eerd_done()?;
+            *word = (self.mem_addr.readl(E1000_EERD)? >> E1000_EEPROM_RW_REG_DATA) as u16;
+        }
+        Ok(())
+    }
+
+    fn e1000_poll_eerd_done(&self) -> Result {
+        for _ in 0..1000 {
+            if self.mem_addr.readl(E1000_EERD)? & E1000_EEPROM_RW_REG_DONE != 0 {
+                return Ok(());
+            }
+            udelay(5);
+        }
+        Err(ETIMEDOUT)
+    }
+
+    /// Bit-bang the microwire EEPROM through EECD, the only interface the C version uses for the 82540.
+    fn e1000_read_eeprom_microwire(&self, offset: u16, data: &mut [u16]) -> Result {
+        self.e1000_acquire_eeprom()?;
+
+        let eecd = self.mem_addr.readl(E1000_EECD)?;
+        let address_bits = if eecd & E1000_EECD_SIZE != 0 { 8 } else { 6 };
+
+        let ret = (|| {
+            for (i, word) in data.iter_mut().enumerate() {
+                /* Send the READ command (opcode + addr) */
+                self.e1000_shift_out_ee_bits(EEPROM_READ_OPCODE_MICROWIRE, 3)?;
+                self.e1000_shift_out_ee_bits(offset + i as u16, address_bits)?;
+
+                /* Read the data.  For microwire, each word requires the
+                 * overhead of eeprom setup and tear-down.
+                 */
+                *word = self.e1000_shift_in_ee_bits(16)?;
+                self.e1000_standby_eeprom()?;
+            }
+            Ok(())
+        })();
+
+        self.e1000_release_eeprom()?;
+        ret
+    }
+
+    fn e1000_acquire_eeprom(&self) -> Result {
+        /* Request EEPROM Access */
+        let mut eecd = self.mem_addr.readl(E1000_EECD)?;
+        eecd |= E1000_EECD_REQ;
+        self.mem_addr.writel(eecd, E1000_EECD)?;
+
+        let mut granted = false;
+        for _ in 0..1000 {
+            eecd = self.mem_addr.readl(E1000_EECD)?;
+            if eecd & E1000_EECD_GNT != 0 {
+                granted = true;
+                break;
+            }
+            udelay(5);
+        }
+        if !granted {
+            eecd &= !E1000_EECD_REQ;
+            self.mem_addr.writel(eecd, E1000_EECD)?;
+            pr_err!("Could not acquire EEPROM grant\n");
+            return Err(ETIMEDOUT);
+        }
+
+        /* Clear SK and DI */
+        eecd &= !(E1000_EECD_DI | E1000_EECD_SK);
+        self.mem_addr.writel(eecd, E1000_EECD)?;
+
+        /* Set CS */
+        eecd |= E1000_EECD_CS;
+        self.mem_addr.writel(eecd, E1000_EECD)?;
+
+        Ok(())
+    }
+
+    fn e1000_release_eeprom(&self) -> Result {
+        let mut eecd = self.mem_addr.readl(E1000_EECD)?;
+
+        /* CS on Microwire is active-high */
+        eecd &= !(E1000_EECD_CS | E1000_EECD_DI);
+        self.mem_addr.writel(eecd, E1000_EECD)?;
+
+        /* Rising edge of clock */
+        eecd = self.e1000_raise_ee_clk(eecd)?;
+        /* Falling edge of clock */
+        eecd = self.e1000_lower_ee_clk(eecd)?;
+
+        /* Stop requesting EEPROM access */
+        eecd &= !E1000_EECD_REQ;
+        self.mem_addr.writel(eecd, E1000_EECD)?;
+
+        Ok(())
+    }
+
+    fn e1000_standby_eeprom(&self) -> Result {
+        let mut eecd = self.mem_addr.readl(E1000_EECD)?;
+
+        /* Deselect EEPROM */
+        eecd &= !(E1000_EECD_CS | E1000_EECD_SK);
+        self.e1000_write_eecd_and_wait(eecd)?;
+
+        /* Clock high */
+        eecd |= E1000_EECD_SK;
+        self.e1000_write_eecd_and_wait(eecd)?;
+
+        /* Select EEPROM */
+        eecd |= E1000_EECD_CS;
+        self.e1000_write_eecd_and_wait(eecd)?;
+
+        /* Clock low */
+        eecd &= !E1000_EECD_SK;
+        self.e1000_write_eecd_and_wait(eecd)?;
+
+        Ok(())
+    }
+
+    /// Shift the lowest `count` bits of `data` out to the EEPROM, most significant bit first.
+    fn e1000_shift_out_ee_bits(&self, data: u16, count: u16) -> Result {
+        let mut eecd = self.mem_addr.readl(E1000_EECD)?;
+        eecd &= !E1000_EECD_DO;
+
+        let mut mask = 1u16 << (count - 1);
+        while mask != 0 {
+            /* A "1" is shifted out to the EEPROM by setting bit "DI" to a "1",
+             * and then raising and then lowering the clock (the SK bit controls
+             * the clock input to the EEPROM).  A "0" is shifted out to the EEPROM
+             * by setting "DI" to "0" and then raising and then lowering the clock.
+             */
+            eecd &= !E1000_EECD_DI;
+            if data & mask != 0 {
+                eecd |= E1000_EECD_DI;
+            }
+            self.e1000_write_eecd_and_wait(eecd)?;
+
+            eecd = self.e1000_raise_ee_clk(eecd)?;
+            eecd = self.e1000_lower_ee_clk(eecd)?;
+
+            mask >>= 1;
+        }
+
+        /* We leave the "DI" bit set to "0" when we leave this routine. */
+        eecd &= !E1000_EECD_DI;
+        self.mem_addr.writel(eecd, E1000_EECD)?;
+
+        Ok(())
+    }
+
+    /// Shift `count` bits in from the EEPROM, most significant bit first.
+    fn e1000_shift_in_ee_bits(&self, count: u16) -> Result<u16> {
+        /* In order to read a register from the EEPROM, we need to shift 'count'
+         * bits in from the EEPROM. Bits are "shifted in" by raising the clock
+         * input to the EEPROM (setting the SK bit), and then reading the value of
+         * the "DO" bit.  During this "shifting in" process the "DI" bit should
+         * always be clear.
+         */
+        let mut eecd = self.mem_addr.readl(E1000_EECD)?;
+        eecd &= !(E1000_EECD_DO | E1000_EECD_DI);
+
+        let mut data = 0u16;
+        for _ in 0..count {
+            data <<= 1;
+            eecd = self.e1000_raise_ee_clk(eecd)?;
+
+            eecd = self.mem_addr.readl(E1000_EECD)?;
+            eecd &= !E1000_EECD_DI;
+            if eecd & E1000_EECD_DO != 0 {
+                data |= 1;
+            }
+
+            eecd = self.e1000_lower_ee_clk(eecd)?;
+        }
+
+        Ok(data)
+    }
+
+    fn e1000_raise_ee_clk(&self, eecd: u32) -> Result<u32> {
+        let eecd = eecd | E1000_EECD_SK;
+        self.e1000_write_eecd_and_wait(eecd)?;
+        Ok(eecd)
+    }
+
+    fn e1000_lower_ee_clk(&self, eecd: u32) -> Result<u32> {
+        let eecd = eecd & !E1000_EECD_SK;
+        self.e1000_write_eecd_and_wait(eecd)?;
+        Ok(eecd)
+    }
+
+    fn e1000_write_eecd_and_wait(&self, eecd: u32) -> Result {
+        self.mem_addr.writel(eecd, E1000_EECD)?;
+        self.e1000_write_flush();
+        // The microwire EEPROM needs at least 50us for each clock edge.
+        udelay(50);
+        Ok(())
+    }
+
+    pub(crate) fn e1000_configure(&self, rx_ring: &RxRingBuf, tx_ring: &TxRingBuf, mac_addr: &[u8; 6], max_frame_size: usize, features: u64) -> Result {
+        self.e1000_configure_rx(rx_ring, mac_addr, max_frame_size)?;
+        self.e1000_set_rx_csum(features & NETIF_F_RXCSUM != 0)?;
+        self.e1000_vlan_mode(features & NETIF_F_HW_VLAN_CTAG_RX != 0)?;
         self.e1000_configure_tx(tx_ring)?;
 
-        // Enable related interrupts
-        self.mem_addr.writel(
-            E1000_ICR_TXDW | E1000_ICR_RXT0 | E1000_ICR_RXDMT0 | E1000_ICR_RXSEQ | E1000_ICR_LSC,
-            E1000_IMS
-        )?;
+        // The interrupts are enabled later by `e1000_irq_enable()`, after the irq handler is registered.
         Ok(())
     }
 
@@ -92,26 +435,22 @@ impl E1000Ops {
         // According to Manual 14.5
 
         // set ring buf head index, tail index and buf size
-        self.mem_addr.writel(0, E1000_TDH)?;
-        self.mem_addr.writel(0, E1000_TDT)?;
-        self.mem_addr.writel((TX_RING_SIZE * 16) as u32, E1000_TDLEN)?;
+        self.mem_addr.writel(tx_ring.next_to_clean as u32, E1000_TDH)?;
+        self.mem_addr.writel(tx_ring.next_to_use as u32, E1000_TDT)?;
+        self.mem_addr.writel((tx_ring.count() * core::mem::size_of::<TxDescEntry>()) as u32, E1000_TDLEN)?;
         // set ring buf start address
         self.mem_addr.writel(tx_ring.desc.get_dma_addr() as u32, E1000_TDBAL)?;
         self.mem_addr.writel(0, E1000_TDBAH)?;
 
-        let tctl = (
-            E1000_TCTL_EN | 
+        let tctl = E1000_TCTL_EN |
             E1000_TCTL_PSP |
-            0x10 << E1000_CT_SHIFT | 
-            0x40 << E1000_COLD_SHIFT
-        );
+            0x10 << E1000_CT_SHIFT |
+            0x40 << E1000_COLD_SHIFT;
         self.mem_addr.writel(tctl, E1000_TCTL)?;
 
-        let tipg = (
-            DEFAULT_82543_TIPG_IPGT_COPPER | 
+        let tipg = DEFAULT_82543_TIPG_IPGT_COPPER |
             DEFAULT_82543_TIPG_IPGR1 << E1000_TIPG_IPGR1_SHIFT |
-            DEFAULT_82543_TIPG_IPGR2 << E1000_TIPG_IPGR2_SHIFT
-        );
+            DEFAULT_82543_TIPG_IPGR2 << E1000_TIPG_IPGR2_SHIFT;
         self.mem_addr.writel(tipg, E1000_TIPG)?;
         
 
@@ -119,41 +458,303 @@ impl E1000Ops {
 
     }
 
-    // fn e1000_setup_rctl(&self) {
+    /// Enable or disable the IP and TCP/UDP checksum offload of received packets. The raw packet checksum in the rx
+    /// descriptors is computed either way, it starts at the network header.
+    pub(crate) fn e1000_set_rx_csum(&self, enable: bool) -> Result {
+        let mut rxcsum = ENET_HEADER_SIZE as u32 & E1000_RXCSUM_PCSS_MASK;
+        if enable {
+            rxcsum |= E1000_RXCSUM_IPOFL | E1000_RXCSUM_TUOFL;
+        }
+        self.mem_addr.writel(rxcsum, E1000_RXCSUM)?;
+        Ok(())
+    }
+
+    /// Enable or disable the VLAN tag stripping and insertion, corresponding to the C version `e1000_vlan_mode()`. The
+    /// hardware can't do only one of them, CTRL.VME is needed for both.
+    pub(crate) fn e1000_vlan_mode(&self, enable: bool) -> Result {
+        let mut ctrl = self.mem_addr.readl(E1000_CTRL)?;
+        if enable {
+            ctrl |= E1000_CTRL_VME;
+        } else {
+            ctrl &= !E1000_CTRL_VME;
+        }
+        self.mem_addr.writel(ctrl, E1000_CTRL)?;
+        Ok(())
+    }
+
+    /// Put `addr` into the receive address register `index`, corresponding to the C version `e1000_rar_set()`. The
+    /// address valid bit must be set or the filter is ignored.
+    pub(crate) fn e1000_rar_set(&self, addr: &[u8; 6], index: usize) -> Result {
+        let ral = u32::from_le_bytes([addr[0], addr[1], addr[2], addr[3]]);
+        let rah = u32::from_le_bytes([addr[4], addr[5], 0, 0]) | E1000_RAH_AV;
+
+        self.mem_addr.writel(ral, E1000_RA + index * 8)?;
+        self.e1000_write_flush();
+        self.mem_addr.writel(rah, E1000_RA + index * 8 + 4)?;
+        self.e1000_write_flush();
+        Ok(())
+    }
+
+    /// Invalidate the receive address register `index`.
+    pub(crate) fn e1000_rar_clear(&self, index: usize) -> Result {
+        self.mem_addr.writel(0, E1000_RA + index * 8)?;
+        self.e1000_write_flush();
+        self.mem_addr.writel(0, E1000_RA + index * 8 + 4)?;
+        self.e1000_write_flush();
+        Ok(())
+    }
+
+    /// The MTA bit of a multicast address, corresponding to the C version `e1000_hash_mc_addr()`.
+    fn e1000_hash_mc_addr(addr: &[u8; 6]) -> u32 {
+        let hash_value = match E1000_MC_FILTER_TYPE {
+            0 => (addr[4] as u32 >> 4) | (addr[5] as u32) << 4,	/* [47:36] */
+            1 => (addr[4] as u32 >> 3) | (addr[5] as u32) << 5,	/* [46:35] */
+            2 => (addr[4] as u32 >> 2) | (addr[5] as u32) << 6,	/* [45:34] */
+            _ => addr[4] as u32 | (addr[5] as u32) << 8,	/* [43:32] */
+        };
+        hash_value & 0xFFF
+    }
+
+    /// Program the unicast and multicast filters, corresponding to the C version `e1000_set_rx_mode()`. The secondary
+    /// unicast addresses take RA[1..] first, then the multicast ones, the rest of the multicast addresses are hashed
+    /// into the MTA. If there are too many unicast addresses, unicast promiscuous mode is used instead. RCTL.VFE is
+    /// left to `e1000_vlan_filter_on_off()`.
+    pub(crate) fn e1000_set_rx_mode(&self, promisc: bool, allmulti: bool, uc_addrs: &mut dyn ExactSizeIterator<Item = [u8; 6]>, mc_addrs: &mut dyn Iterator<Item = [u8; 6]>) -> Result {
+        let use_uc = !promisc && uc_addrs.len() < E1000_RAR_ENTRIES;
+
+        let mut rctl = self.mem_addr.readl(E1000_RCTL)?;
+        rctl &= !(E1000_RCTL_UPE | E1000_RCTL_MPE);
+        if !use_uc {
+            rctl |= E1000_RCTL_UPE;
+        }
+        if promisc || allmulti {
+            rctl |= E1000_RCTL_MPE;
+        }
+        self.mem_addr.writel(rctl, E1000_RCTL)?;
+
+        let mut index = 1;
+        if use_uc {
+            for addr in uc_addrs {
+                self.e1000_rar_set(&addr, index)?;
+                index += 1;
+            }
+        }
+
+        let mut mcarray = [0u32; E1000_NUM_MTA_REGISTERS];
+        for addr in mc_addrs {
+            if index < E1000_RAR_ENTRIES {
+                self.e1000_rar_set(&addr, index)?;
+                index += 1;
+            } else {
+                let hash_value = Self::e1000_hash_mc_addr(&addr);
+                mcarray[(hash_value >> 5) as usize & 0x7F] |= 1 << (hash_value & 0x1F);
+            }
+        }
+
+        for i in index..E1000_RAR_ENTRIES {
+            self.e1000_rar_clear(i)?;
+        }
+
+        // write the hash table completely, write from bottom to avoid both stupid write combining chipsets, and
+        // flushing each write
+        for (i, mta) in mcarray.iter().enumerate().rev() {
+            self.mem_addr.writel(*mta, E1000_MTA + i * 4)?;
+        }
+        self.e1000_write_flush();
 
-    // }
+        Ok(())
+    }
+
+    /// Write a word of the VLAN filter table, corresponding to the C version `e1000_write_vfta()`.
+    pub(crate) fn e1000_write_vfta(&self, offset: usize, value: u32) -> Result {
+        self.mem_addr.writel(value, E1000_VFTA + offset * 4)?;
+        self.e1000_write_flush();
+        Ok(())
+    }
 
-    fn e1000_configure_rx(&self, rx_ring: &RxRingBuf) -> Result {
+    /// Drop the tagged packets whose VLAN ID is not in the VLAN filter table, or accept all of them. Corresponding to
+    /// the C version `e1000_vlan_filter_on_off()`.
+    pub(crate) fn e1000_vlan_filter_on_off(&self, filter_on: bool) -> Result {
+        let mut rctl = self.mem_addr.readl(E1000_RCTL)?;
+        if filter_on {
+            rctl |= E1000_RCTL_VFE;
+            rctl &= !E1000_RCTL_CFIEN;
+        } else {
+            rctl &= !E1000_RCTL_VFE;
+        }
+        self.mem_addr.writel(rctl, E1000_RCTL)?;
+        Ok(())
+    }
+
+    /// Compute the RCTL value for buffers of `rx_buffer_len` bytes, corresponding to the C version `e1000_setup_rctl()`.
+    /// `rx_buffer_len` must be one of the `E1000_RXBUFFER_*` sizes.
+    fn e1000_setup_rctl(rx_buffer_len: usize, max_frame_size: usize) -> u32 {
+        let mut rctl = E1000_RCTL_EN | E1000_RCTL_BAM | E1000_RCTL_SECRC | E1000_MC_FILTER_TYPE << E1000_RCTL_MO_SHIFT;
+
+        // Frames longer than 1522 bytes are dropped by the hardware unless LPE is set.
+        if max_frame_size > MAXIMUM_ETHERNET_VLAN_SIZE {
+            rctl |= E1000_RCTL_LPE;
+        }
+
+        // The sizes above 2048 reuse the encodings of the small ones, with BSEX multiplying them by 16.
+        rctl |= match rx_buffer_len {
+            E1000_RXBUFFER_256 => E1000_RCTL_SZ_256,
+            E1000_RXBUFFER_512 => E1000_RCTL_SZ_512,
+            E1000_RXBUFFER_1024 => E1000_RCTL_SZ_1024,
+            E1000_RXBUFFER_4096 => E1000_RCTL_SZ_4096 | E1000_RCTL_BSEX,
+            E1000_RXBUFFER_8192 => E1000_RCTL_SZ_8192 | E1000_RCTL_BSEX,
+            E1000_RXBUFFER_16384 => E1000_RCTL_SZ_16384 | E1000_RCTL_BSEX,
+            _ => E1000_RCTL_SZ_2048,
+        };
+
+        rctl
+    }
+
+    fn e1000_configure_rx(&self, rx_ring: &RxRingBuf, mac_addr: &[u8; 6], max_frame_size: usize) -> Result {
         // According to Manual 14.4
 
-        // According to MIT6.828 Exercise 10, hardcode to QEMU's MAC address.
-        // 52:54:00:12:34:56
-        self.mem_addr.writel(0x12005452, E1000_RA)?;      //RAL
-        self.mem_addr.writel(0x5634 | (1 << 31), E1000_RA + 4)?;  //RAH
+        // Program the MAC address into RA[0]. The other address filters are programmed by `e1000_set_rx_mode()`.
+        self.e1000_rar_set(mac_addr, 0)?;
 
-        for i in 0..128 {
+        for i in 0..E1000_NUM_MTA_REGISTERS {
             self.mem_addr.writel(0, E1000_MTA + i * 4)?;
         }
 
         
-        self.mem_addr.writel(0, E1000_RDH)?;
-        self.mem_addr.writel((RX_RING_SIZE - 1) as u32, E1000_RDT)?;
-        self.mem_addr.writel((RX_RING_SIZE * 16) as u32, E1000_RDLEN)?;
+        self.mem_addr.writel(rx_ring.next_to_clean as u32, E1000_RDH)?;
+        self.mem_addr.writel(rx_ring.next_to_use as u32, E1000_RDT)?;
+        self.mem_addr.writel((rx_ring.count() * core::mem::size_of::<RxDescEntry>()) as u32, E1000_RDLEN)?;
         self.mem_addr.writel(rx_ring.desc.get_dma_addr() as u32, E1000_RDBAL)?;
         self.mem_addr.writel(0, E1000_RDBAH)?;
 
-        let rctl = (
-            E1000_RCTL_EN | 
-            E1000_RCTL_BAM | 
-            E1000_RCTL_SZ_2048 | 
-            E1000_RCTL_SECRC
-        );
-        self.mem_addr.writel(rctl, E1000_RCTL)?;
+        self.mem_addr.writel(Self::e1000_setup_rctl(rx_ring.rx_buffer_len, max_frame_size), E1000_RCTL)?;
 
-        // Disable RDTR and RADV timer, since we use NAPI, we don't need hardware to help us decrease interrupts.
-        self.mem_addr.writel(0, E1000_RDTR)?;
-        self.mem_addr.writel(0, E1000_RADV)?;
-        
+        // The RDTR and RADV timers are set by `e1000_set_coalesce()`.
+        Ok(())
+    }
+
+    /// Read link up, speed and duplex from the STATUS register, correspond to C version `e1000_check_for_link` and
+    /// `e1000_get_speed_and_duplex`. QEMU and the 82540 copper PHY set these bits once auto-negotiation is done.
+    pub(crate) fn e1000_check_link(&self) -> Result<E1000LinkStatus> {
+        let status = self.mem_addr.readl(E1000_STATUS)?;
+
+        let speed = if status & E1000_STATUS_SPEED_1000 != 0 {
+            1000
+        } else if status & E1000_STATUS_SPEED_100 != 0 {
+            100
+        } else {
+            10
+        };
+
+        Ok(E1000LinkStatus {
+            up: status & E1000_STATUS_LU != 0,
+            speed,
+            full_duplex: status & E1000_STATUS_FD != 0,
+        })
+    }
+
+    /// Add the clear-on-read statistics registers to `stats`, correspond to C version `e1000_update_stats`.
+    pub(crate) fn e1000_update_stats(&self, stats: &mut E1000HwStats) -> Result {
+        let read = |reg| -> Result<u64> { Ok(self.mem_addr.readl(reg)? as u64) };
+
+        stats.crcerrs += read(E1000_CRCERRS)?;
+        stats.algnerrc += read(E1000_ALGNERRC)?;
+        stats.symerrs += read(E1000_SYMERRS)?;
+        stats.rxerrc += read(E1000_RXERRC)?;
+        stats.mpc += read(E1000_MPC)?;
+        stats.scc += read(E1000_SCC)?;
+        stats.ecol += read(E1000_ECOL)?;
+        stats.mcc += read(E1000_MCC)?;
+        stats.latecol += read(E1000_LATECOL)?;
+        stats.colc += read(E1000_COLC)?;
+        stats.dc += read(E1000_DC)?;
+        stats.tncrs += read(E1000_TNCRS)?;
+        stats.cexterr += read(E1000_CEXTERR)?;
+        stats.rlec += read(E1000_RLEC)?;
+        stats.gprc += read(E1000_GPRC)?;
+        stats.bprc += read(E1000_BPRC)?;
+        stats.mprc += read(E1000_MPRC)?;
+        stats.gptc += read(E1000_GPTC)?;
+
+        /* For the 64-bit byte counters the low dword must be read first.
+         * Both registers clear on the read of the high dword.
+         */
+        let gorcl = read(E1000_GORCL)?;
+        stats.gorc += gorcl | (read(E1000_GORCH)? << 32);
+        let gotcl = read(E1000_GOTCL)?;
+        stats.gotc += gotcl | (read(E1000_GOTCH)? << 32);
+
+        stats.rnbc += read(E1000_RNBC)?;
+        stats.ruc += read(E1000_RUC)?;
+        stats.roc += read(E1000_ROC)?;
+        stats.tpr += read(E1000_TPR)?;
+        stats.tpt += read(E1000_TPT)?;
+        stats.mptc += read(E1000_MPTC)?;
+        stats.bptc += read(E1000_BPTC)?;
+
+        Ok(())
+    }
+
+    /// Program the interrupt delay timers and the throttling rate, corresponding to the C version
+    /// `e1000_configure_rx()`, `e1000_configure_tx()` and `e1000_set_coalesce()`. Nothing is written if any of the
+    /// values is out of range. The tx delay timers only apply to the descriptors with `E1000_TXD_CMD_IDE`.
+    pub(crate) fn e1000_set_coalesce(&self, coal: &E1000Coalesce) -> Result {
+        let rdtr = Self::e1000_usecs_to_delay(coal.rx_usecs)?;
+        let radv = Self::e1000_usecs_to_delay(coal.rx_usecs_high)?;
+        let tidv = Self::e1000_usecs_to_delay(coal.tx_usecs)?;
+        let tadv = Self::e1000_usecs_to_delay(coal.tx_usecs_high)?;
+        let itr = Self::e1000_ints_to_itr(coal.itr)?;
+
+        self.mem_addr.writel(rdtr, E1000_RDTR)?;
+        self.mem_addr.writel(radv, E1000_RADV)?;
+        self.mem_addr.writel(tidv, E1000_TIDV)?;
+        self.mem_addr.writel(tadv, E1000_TADV)?;
+        self.mem_addr.writel(itr, E1000_ITR)?;
+        Ok(())
+    }
+
+    /// Program the rx interrupt moderation picked by `net_dim()`, corresponding to the C version `e1000_set_itr()`.
+    /// The other timers keep the values set by `e1000_set_coalesce()`.
+    pub(crate) fn e1000_set_rx_moderation(&self, rx_usecs: u32, ints_per_sec: u32) -> Result {
+        let rdtr = Self::e1000_usecs_to_delay(rx_usecs)?;
+        let itr = Self::e1000_ints_to_itr(ints_per_sec)?;
+
+        self.mem_addr.writel(rdtr, E1000_RDTR)?;
+        self.mem_addr.writel(itr, E1000_ITR)?;
+        Ok(())
+    }
+
+    /// The delay timers count in units of 1.024us.
+    fn e1000_usecs_to_delay(usecs: u32) -> Result<u32> {
+        let delay = (usecs as u64 * 1000 / 1024) as u32;
+        if delay > E1000_MAX_INT_DELAY {
+            return Err(EINVAL);
+        }
+        Ok(delay)
+    }
+
+    /// ITR holds the minimum interval between interrupts in units of 256ns, same as the C version.
+    fn e1000_ints_to_itr(ints_per_sec: u32) -> Result<u32> {
+        if ints_per_sec == 0 {
+            return Ok(0);
+        }
+        let itr = 1_000_000_000 / (ints_per_sec as u64 * 256);
+        if itr > E1000_MAX_ITR as u64 {
+            return Err(EINVAL);
+        }
+        Ok(itr as u32)
+    }
+
+    /// Whether the transmission is paused by a flow control XOFF frame, the tx ring doesn't move meanwhile.
+    pub(crate) fn e1000_tx_paused(&self) -> Result<bool> {
+        Ok(self.mem_addr.readl(E1000_STATUS)? & E1000_STATUS_TXOFF != 0)
+    }
+
+    /// Raise the rx descriptor minimum threshold interrupt by software, so that the napi poll cleans and refills the
+    /// rx ring.
+    pub(crate) fn e1000_trigger_rx_irq(&self) -> Result {
+        self.mem_addr.writel(E1000_ICR_RXDMT0, E1000_ICS)?;
+        self.e1000_write_flush();
         Ok(())
     }
 
@@ -174,14 +775,6 @@ impl E1000Ops {
     }
 
 
-    pub(crate) fn e1000_read_rx_queue_head(&self) -> u32 {
-        self.mem_addr.readl(E1000_RDH).unwrap()
-    }
-
-    pub(crate) fn e1000_read_rx_queue_tail(&self) -> u32 {
-        self.mem_addr.readl(E1000_RDT).unwrap()
-    }
-
     pub(crate) fn e1000_write_rx_queue_tail(&self, val: u32) {
         self.mem_addr.writel(val, E1000_RDT).unwrap()
     }
@@ -0,0 +1,21 @@
+//! The C helpers built from `e1000_helpers.c`, for the static inline functions and macros of the kernel that bindgen
+//! can't see.
+
+use kernel::bindings;
+
+extern "C" {
+    /// The C version `dma_mapping_error()`.
+    pub(crate) fn r4l_e1000_dma_mapping_error(dev: *mut bindings::device, dma_addr: bindings::dma_addr_t) -> core::ffi::c_int;
+
+    /// The C macro `INIT_WORK()`.
+    pub(crate) fn r4l_e1000_init_work(work: *mut bindings::work_struct, func: bindings::work_func_t);
+
+    /// The C macro `INIT_DELAYED_WORK()`.
+    pub(crate) fn r4l_e1000_init_delayed_work(dwork: *mut bindings::delayed_work, func: bindings::work_func_t);
+
+    /// The C version `netif_tx_disable()`.
+    pub(crate) fn r4l_e1000_netif_tx_disable(dev: *mut bindings::net_device);
+
+    /// The C version `dev_alloc_pages()`, a compound page of `1 << order` pages for rx buffers, NULL on failure.
+    pub(crate) fn r4l_e1000_dev_alloc_pages(order: core::ffi::c_uint) -> *mut bindings::page;
+}
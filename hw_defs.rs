@@ -11,6 +11,29 @@ pub(crate) struct TxDescEntry {
     pub(crate) special: u16,
 }
 
+impl TxDescEntry {
+    /// View this descriptor as a context descriptor, they take the same slots in the tx ring.
+    pub(crate) fn as_context(&mut self) -> &mut TxContextDescEntry {
+        // SAFETY: both are `repr(C)` structs of 16 bytes made up of integers only, any bit pattern is valid for them.
+        unsafe{&mut *(self as *mut TxDescEntry as *mut TxContextDescEntry)}
+    }
+}
+
+// Defined in intel chip manual section 3.3.6
+#[repr(C)]
+pub(crate) struct TxContextDescEntry {
+    pub(crate) ipcss: u8,
+    pub(crate) ipcso: u8,
+    pub(crate) ipcse: u16,
+    pub(crate) tucss: u8,
+    pub(crate) tucso: u8,
+    pub(crate) tucse: u16,
+    pub(crate) cmd_and_length: u32,
+    pub(crate) status: u8,
+    pub(crate) hdr_len: u8,
+    pub(crate) mss: u16,
+}
+
 
 // Defined in intel chip manual section 3.2.3
 #[repr(C)]
@@ -2,11 +2,81 @@ use kernel::net::SkBuff;
 use kernel::prelude::*;
 use kernel::dma;
 use core::cell::RefCell;
+use core::sync::atomic::{compiler_fence, Ordering};
 use crate::hw_defs::{RxDescEntry,TxDescEntry};
+use crate::skb::{FragDma, RxPage};
 
 /// A pair made up of a SkBuff and it's dma mapping
 pub(crate) type SkbDma = (dma::MapSingle::<u8>, ARef<SkBuff>);
 
+/// A rx descriptor's buffer. Buffers of up to 2048 bytes are skb heads, the larger ones are pages, same as the C
+/// version uses `e1000_clean_rx_irq()` and `e1000_clean_jumbo_rx_irq()` for them.
+pub(crate) enum RxBuffer {
+    Skb(SkbDma),
+    Page(RxPage),
+}
+
+impl RxBuffer {
+    pub(crate) fn dma_handle(&self) -> u64 {
+        match self {
+            RxBuffer::Skb((ms, _)) => ms.dma_handle as u64,
+            RxBuffer::Page(page) => page.dma_handle,
+        }
+    }
+}
+
+/// The dma mapping of a tx buffer, the linear part of a skb is mapped with `dma::MapSingle`, and the page frags with
+/// `FragDma`.
+pub(crate) enum TxDma {
+    Single(dma::MapSingle::<u8>),
+    Frag(FragDma),
+}
+
+impl TxDma {
+    pub(crate) fn dma_handle(&self) -> u64 {
+        match self {
+            TxDma::Single(ms) => ms.dma_handle as u64,
+            TxDma::Frag(fd) => fd.dma_handle,
+        }
+    }
+}
+
+/// A tx descriptor's buffer. A packet takes one of these for every descriptor it uses.
+pub(crate) struct TxBuffer {
+    /// None for context descriptors, they have no buffer.
+    pub(crate) dma: Option<TxDma>,
+    /// Only set on the last descriptor of a packet, the skb is released when the packet is sent.
+    pub(crate) skb: Option<ARef<SkBuff>>,
+    /// Only set on the last descriptor of a packet, the number of packets and bytes put on the wire, reported to
+    /// BQL. A TSO packet is sent as several segments, each of them with a copy of the headers.
+    pub(crate) segs: u32,
+    pub(crate) bytecount: u32,
+    /// Only set on the first descriptor of a packet, the index of its last descriptor. RS is only set on the last
+    /// one, so the DD bit is only reported there.
+    pub(crate) next_to_watch: Option<usize>,
+}
+
+// There is no `dma_wmb()` here. The descriptors are only handed to the device by writing the tail register with
+// `writel()`, which orders the memory writes before it with the MMIO write, as the C driver relies on too.
+
+/// Make sure the other descriptor fields are read after the DD bit, corresponding to the C version `dma_rmb()`. The
+/// R4L crate doesn't provide DMA barriers now, so this is the kernel's definition for the architectures we run on. A CPU
+/// fence is not enough for the loads from the device on arm64.
+#[cfg(target_arch = "x86_64")]
+pub(crate) fn dma_rmb() {
+    // x86 doesn't reorder loads from coherent memory, the kernel's `dma_rmb()` is `barrier()` there.
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(target_arch = "aarch64")]
+pub(crate) fn dma_rmb() {
+    // SAFETY: a barrier instruction, it doesn't touch any memory.
+    unsafe{core::arch::asm!("dmb oshld", options(nostack, preserves_flags))};
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+compile_error!("dma_rmb() is not implemented for this architecture");
+
 /// A slice view into `dma::Allocation`.
 pub(crate) struct DmaAllocSlice<T> {
     desc: dma::Allocation::<T>,
@@ -22,19 +92,26 @@ impl<T> DmaAllocSlice<T> {
     pub(crate) fn get_dma_addr(&self) -> usize {
         self.desc.dma_handle as usize
     }
-
-    pub(crate) fn get_cpu_addr(&self) -> usize {
-        self.desc.cpu_addr as usize
-    }
 }
 
-pub(crate) struct RingBuf<T> {
+pub(crate) struct RingBuf<T, B> {
     pub(crate) desc: DmaAllocSlice<T>,
-    pub(crate) buf: RefCell<Vec<Option<SkbDma>>>,
+    pub(crate) buf: RefCell<Vec<Option<B>>>,
+    /// The next descriptor to be given to the hardware, mirrors the tail register so it never has to be read back.
+    pub(crate) next_to_use: usize,
+    /// The next descriptor to be checked for the DD bit.
     pub(crate) next_to_clean: usize,
+    /// Rx only, the first part of a frame whose EOP descriptor hasn't been received yet.
+    pub(crate) rx_skb_top: Option<ARef<SkBuff>>,
+    /// Rx only, set when a part of the current frame is dropped, the rest of it is dropped up to the EOP descriptor.
+    pub(crate) discarding: bool,
+    /// Rx only, the size of the buffers, picked from the MTU when the ring is allocated.
+    pub(crate) rx_buffer_len: usize,
+    /// Tx only, `next_to_clean` seen by the last watchdog run while there were pending descriptors.
+    pub(crate) watchdog_next_to_clean: Option<usize>,
 }
 
-impl<T> RingBuf<T> {
+impl<T, B> RingBuf<T, B> {
     pub(crate) fn new(desc: dma::Allocation::<T>, len: usize) -> Self {
         let buf = RefCell::new(Vec::new());
         
@@ -49,9 +126,24 @@ impl<T> RingBuf<T> {
             desc,
             count: len,
         };
-        Self {desc, buf, next_to_clean:0}
+        Self {desc, buf, next_to_use:0, next_to_clean:0, rx_skb_top: None, discarding: false, rx_buffer_len: 0, watchdog_next_to_clean: None}
+    }
+}
+
+impl<T, B> RingBuf<T, B> {
+    /// Number of descriptors in the ring.
+    pub(crate) fn count(&self) -> usize {
+        self.desc.count
+    }
+
+    /// Number of descriptors that can still be given to the hardware, corresponding to the C macro
+    /// `E1000_DESC_UNUSED`. One descriptor is always left unused, otherwise a full ring couldn't be told apart from an
+    /// empty one.
+    pub(crate) fn unused_count(&self) -> usize {
+        let count = self.desc.count;
+        (self.next_to_clean + count - self.next_to_use - 1) % count
     }
 }
 
-pub(crate) type RxRingBuf = RingBuf<RxDescEntry>;
-pub(crate) type TxRingBuf = RingBuf<TxDescEntry>;
\ No newline at end of file
+pub(crate) type RxRingBuf = RingBuf<RxDescEntry, RxBuffer>;
+pub(crate) type TxRingBuf = RingBuf<TxDescEntry, TxBuffer>;
\ No newline at end of file
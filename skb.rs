@@ -0,0 +1,382 @@
+use kernel::bindings;
+use kernel::device::RawDevice;
+use kernel::net::SkBuff;
+use kernel::prelude::*;
+use crate::helpers;
+
+/// A page fragment of a non-linear skb.
+pub(crate) struct SkbFrag {
+    pub(crate) page: *mut bindings::page,
+    pub(crate) offset: usize,
+    pub(crate) len: usize,
+}
+
+/// Accessors of `struct sk_buff` that the R4L crate doesn't support now, the raw C bindings are used here.
+pub(crate) trait SkBuffExt {
+    /// Number of page frags, the C version `skb_shinfo(skb)->nr_frags`.
+    fn nr_frags(&self) -> usize;
+
+    /// The `i`th page frag, `i` must be less than `nr_frags()`.
+    fn frag(&self, i: usize) -> SkbFrag;
+
+    /// The C version `skb->ip_summed`, one of the `bindings::CHECKSUM_*` values.
+    fn ip_summed(&self) -> u32;
+
+    /// Offset from `data` where the checksum starts, the C version `skb_checksum_start_offset()`. Only valid for
+    /// `CHECKSUM_PARTIAL` skbs.
+    fn csum_start_offset(&self) -> usize;
+
+    /// Offset of the checksum field from the checksum start, the C version `skb->csum_offset`. Only valid for
+    /// `CHECKSUM_PARTIAL` skbs.
+    fn csum_offset(&self) -> usize;
+
+    /// Offset from `data` of the network header, the C version `skb_network_offset()`.
+    fn network_offset(&self) -> usize;
+
+    /// The ethertype in host byte order, the C version `ntohs(skb->protocol)`.
+    fn protocol(&self) -> u16;
+
+    /// The protocol of the payload of an IPv4 or IPv6 packet, the C version `ip_hdr(skb)->protocol` and
+    /// `ipv6_hdr(skb)->nexthdr`. None for other packets.
+    fn ip_protocol(&self) -> Option<u8>;
+
+    /// Compute the checksum in software, the C version `skb_checksum_help()`.
+    fn checksum_help(&self) -> Result;
+
+    /// The MSS of a GSO skb, 0 for other skbs. The C version `skb_shinfo(skb)->gso_size`.
+    fn gso_size(&self) -> usize;
+
+    /// Number of segments of a GSO skb, the C version `skb_shinfo(skb)->gso_segs`.
+    fn gso_segs(&self) -> usize;
+
+    /// The `SKB_GSO_*` flags, the C version `skb_shinfo(skb)->gso_type`.
+    fn gso_type(&self) -> u32;
+
+    /// Offset from `data` of the transport header, the C version `skb_transport_offset()`.
+    fn transport_offset(&self) -> usize;
+
+    /// Length of all the headers up to the end of the TCP header, the C version `skb_tcp_all_headers()`.
+    fn tcp_all_headers(&self) -> usize;
+
+    /// Make the headers writable, the C version `skb_cow_head(skb, 0)`.
+    fn cow_head(&self) -> Result;
+
+    /// Write a 16-bit value in network byte order at `offset` from `data`. The headers must be writable, see
+    /// `cow_head()`.
+    fn write_be16(&self, offset: usize, val: u16);
+
+    /// The VLAN tag to be inserted by the hardware, the C version `skb_vlan_tag_get()`. None if
+    /// `skb_vlan_tag_present()` is false.
+    fn vlan_tag(&self) -> Option<u16>;
+
+    /// Pass a VLAN tag stripped by the hardware to the stack, the C version `__vlan_hwaccel_put_tag()`. `proto` is in
+    /// host byte order.
+    fn vlan_hwaccel_put_tag(&self, proto: u16, tci: u16);
+
+    /// Tell the stack the checksums have been verified, `skb->ip_summed = CHECKSUM_UNNECESSARY`.
+    fn set_csum_unnecessary(&self);
+
+    /// Tell the stack `csum` is the checksum of the whole packet from `data`, `skb->ip_summed = CHECKSUM_COMPLETE`.
+    fn set_csum_complete(&self, csum: u32);
+
+    /// Make sure the first `len` bytes are in the linear part, copying them from the page frags if needed. The C
+    /// version `pskb_may_pull()`.
+    fn may_pull(&self, len: usize) -> Result;
+}
+
+fn as_raw(skb: &SkBuff) -> *mut bindings::sk_buff {
+    skb as *const SkBuff as *mut bindings::sk_buff
+}
+
+/// Offset of `data` from `head`, the C version `skb_headroom()`.
+fn headroom(skb: &SkBuff) -> usize {
+    let skb = as_raw(skb);
+    // SAFETY: `skb` is valid.
+    unsafe{(*skb).data.offset_from((*skb).head) as usize}
+}
+
+/// `csum_start` and `csum_offset` of `skb`. They share a union with `csum` in the C struct, the field path is the
+/// one generated by bindgen for the anonymous unions.
+fn csum_fields(skb: &SkBuff) -> (usize, usize) {
+    let skb = as_raw(skb);
+    // SAFETY: `skb` is valid, the union is only read as `csum_start` and `csum_offset` for `CHECKSUM_PARTIAL` skbs.
+    unsafe{
+        let csum = &(*skb).__bindgen_anon_5.headers.__bindgen_anon_3.__bindgen_anon_1;
+        (csum.csum_start as usize, csum.csum_offset as usize)
+    }
+}
+
+/// Set `csum` of `skb`, it's in the same union as `csum_start` and `csum_offset`.
+fn set_csum(skb: &SkBuff, csum: u32) {
+    let skb = as_raw(skb);
+    // SAFETY: `skb` is valid, and it's owned by the rx path until it's passed to the stack.
+    unsafe{(*skb).__bindgen_anon_5.headers.__bindgen_anon_3.csum = csum};
+}
+
+/// The `vlan_all` union of `skb`, made up of `vlan_proto` and `vlan_tci`. The field path is the one generated by
+/// bindgen for the anonymous unions.
+fn vlan_fields(skb: &SkBuff) -> *mut bindings::sk_buff__bindgen_ty_5__bindgen_ty_1__bindgen_ty_2 {
+    let skb = as_raw(skb);
+    // SAFETY: `skb` is valid.
+    unsafe{core::ptr::addr_of_mut!((*skb).__bindgen_anon_5.headers.__bindgen_anon_2)}
+}
+
+/// The C macro `skb_shinfo()`. 64-bit kernels keep `end` as an offset from `head`.
+fn shinfo(skb: &SkBuff) -> *mut bindings::skb_shared_info {
+    let skb = as_raw(skb);
+    // SAFETY: `skb` is valid, and the shared info always follows the linear buffer.
+    unsafe{(*skb).head.add((*skb).end as usize) as *mut bindings::skb_shared_info}
+}
+
+impl SkBuffExt for SkBuff {
+    fn nr_frags(&self) -> usize {
+        // SAFETY: the shared info lives as long as the skb.
+        unsafe{(*shinfo(self)).nr_frags as usize}
+    }
+
+    fn frag(&self, i: usize) -> SkbFrag {
+        assert!(i < self.nr_frags());
+        // SAFETY: `i` is checked above, the frags are not changed while we hold a reference to the skb.
+        let frag = unsafe{&(*shinfo(self)).frags[i]};
+        SkbFrag {
+            page: frag.bv_page,
+            offset: frag.bv_offset as usize,
+            len: frag.bv_len as usize,
+        }
+    }
+
+    fn ip_summed(&self) -> u32 {
+        // SAFETY: `self` is valid, `ip_summed` is a bitfield, bindgen generates the getter for it.
+        unsafe{(*as_raw(self)).ip_summed() as u32}
+    }
+
+    fn csum_start_offset(&self) -> usize {
+        csum_fields(self).0 - headroom(self)
+    }
+
+    fn csum_offset(&self) -> usize {
+        csum_fields(self).1
+    }
+
+    fn network_offset(&self) -> usize {
+        // SAFETY: `self` is valid.
+        let network_header = unsafe{(*as_raw(self)).network_header as usize};
+        network_header - headroom(self)
+    }
+
+    fn protocol(&self) -> u16 {
+        // SAFETY: `self` is valid.
+        u16::from_be(unsafe{(*as_raw(self)).protocol})
+    }
+
+    fn ip_protocol(&self) -> Option<u8> {
+        // Offset of `protocol` in `struct iphdr` and `nexthdr` in `struct ipv6hdr`.
+        let offset = match self.protocol() as u32 {
+            bindings::ETH_P_IP => 9,
+            bindings::ETH_P_IPV6 => 6,
+            _ => return None,
+        };
+        self.head_data().get(self.network_offset() + offset).copied()
+    }
+
+    fn checksum_help(&self) -> Result {
+        // SAFETY: `self` is valid, and the caller owns it in `start_xmit()`.
+        let ret = unsafe{bindings::skb_checksum_help(as_raw(self))};
+        if ret != 0 {
+            return Err(kernel::error::Error::from_kernel_errno(ret));
+        }
+        Ok(())
+    }
+
+    fn gso_size(&self) -> usize {
+        // SAFETY: the shared info lives as long as the skb.
+        unsafe{(*shinfo(self)).gso_size as usize}
+    }
+
+    fn gso_segs(&self) -> usize {
+        // SAFETY: the shared info lives as long as the skb.
+        unsafe{(*shinfo(self)).gso_segs as usize}
+    }
+
+    fn gso_type(&self) -> u32 {
+        // SAFETY: the shared info lives as long as the skb.
+        unsafe{(*shinfo(self)).gso_type as u32}
+    }
+
+    fn transport_offset(&self) -> usize {
+        // SAFETY: `self` is valid.
+        let transport_header = unsafe{(*as_raw(self)).transport_header as usize};
+        transport_header - headroom(self)
+    }
+
+    fn tcp_all_headers(&self) -> usize {
+        // The data offset of the TCP header is the high 4 bits of byte 12, in 32-bit words.
+        let doff = self.head_data()[self.transport_offset() + 12] >> 4;
+        self.transport_offset() + doff as usize * 4
+    }
+
+    fn cow_head(&self) -> Result {
+        let skb = as_raw(self);
+        // SAFETY: `self` is valid, and the caller owns it in `start_xmit()`. A cloned skb shares its data with the
+        // clones, it's copied before the headers are changed.
+        let ret = unsafe{
+            if (*skb).cloned() == 0 {
+                return Ok(());
+            }
+            bindings::pskb_expand_head(skb, 0, 0, bindings::GFP_ATOMIC)
+        };
+        if ret != 0 {
+            return Err(kernel::error::Error::from_kernel_errno(ret));
+        }
+        Ok(())
+    }
+
+    fn write_be16(&self, offset: usize, val: u16) {
+        assert!(offset + 2 <= self.head_data().len());
+        // SAFETY: the offset is checked above, and the headers are made writable by the caller.
+        unsafe{
+            let ptr = (*as_raw(self)).data.add(offset) as *mut [u8; 2];
+            ptr.write_unaligned(val.to_be_bytes());
+        }
+    }
+
+    fn vlan_tag(&self) -> Option<u16> {
+        // SAFETY: `vlan_fields()` returns a valid pointer, `vlan_all` is 0 if there is no tag.
+        unsafe{
+            let vlan = vlan_fields(self);
+            if (*vlan).vlan_all == 0 {
+                return None;
+            }
+            Some((*vlan).__bindgen_anon_1.vlan_tci)
+        }
+    }
+
+    fn vlan_hwaccel_put_tag(&self, proto: u16, tci: u16) {
+        // SAFETY: `vlan_fields()` returns a valid pointer, and the skb is owned by the rx path until it's passed to
+        // the stack.
+        unsafe{
+            let vlan = vlan_fields(self);
+            (*vlan).__bindgen_anon_1.vlan_proto = proto.to_be();
+            (*vlan).__bindgen_anon_1.vlan_tci = tci;
+        }
+    }
+
+    fn set_csum_unnecessary(&self) {
+        // SAFETY: `self` is valid, and it's owned by the rx path until it's passed to the stack.
+        unsafe{(*as_raw(self)).set_ip_summed(bindings::CHECKSUM_UNNECESSARY as u8)};
+    }
+
+    fn set_csum_complete(&self, csum: u32) {
+        set_csum(self, csum);
+        // SAFETY: `self` is valid, and it's owned by the rx path until it's passed to the stack.
+        unsafe{(*as_raw(self)).set_ip_summed(bindings::CHECKSUM_COMPLETE as u8)};
+    }
+
+    fn may_pull(&self, len: usize) -> Result {
+        let headlen = self.head_data().len();
+        if len <= headlen {
+            return Ok(());
+        }
+        if len > self.len() as usize {
+            return Err(kernel::error::code::EINVAL);
+        }
+        // SAFETY: `self` is valid, and it's owned by the rx path until it's passed to the stack.
+        if unsafe{bindings::__pskb_pull_tail(as_raw(self), (len - headlen) as _)}.is_null() {
+            return Err(kernel::error::code::ENOMEM);
+        }
+        Ok(())
+    }
+}
+
+/// The dma mapping of (a part of) a page frag, corresponding to the C version `skb_frag_dma_map()`. `dma::MapSingle`
+/// only takes virtual addresses, which highmem pages don't have. The mapping is released when this is dropped.
+pub(crate) struct FragDma {
+    dev: *mut bindings::device,
+    pub(crate) dma_handle: u64,
+    len: usize,
+}
+
+impl FragDma {
+    pub(crate) fn try_new(dev: &dyn RawDevice, frag: &SkbFrag, offset: usize, len: usize) -> Result<Self> {
+        let dev = dev.raw_device();
+        // SAFETY: `dev` is valid, and the page is kept alive by the skb until the mapping is dropped.
+        let dma_handle = unsafe{
+            bindings::dma_map_page_attrs(dev, frag.page, frag.offset + offset, len, bindings::dma_data_direction_DMA_TO_DEVICE, 0)
+        };
+        // SAFETY: `dev` is valid.
+        if unsafe{helpers::r4l_e1000_dma_mapping_error(dev, dma_handle)} != 0 {
+            return Err(kernel::error::code::ENOMEM);
+        }
+        Ok(Self{dev, dma_handle, len})
+    }
+}
+
+impl Drop for FragDma {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is created in `try_new()` with the same device, size and direction.
+        unsafe{
+            bindings::dma_unmap_page_attrs(self.dev, self.dma_handle, self.len, bindings::dma_data_direction_DMA_TO_DEVICE, 0);
+        }
+    }
+}
+
+/// A rx buffer made up of `1 << order` pages, mapped for the device to write to, corresponding to the `rxbuf.page` of
+/// the C version `struct e1000_rx_buffer`. The received data is handed over to a skb as a page frag, so buffers
+/// larger than a skb head can be used. The page is unmapped and freed when this is dropped.
+pub(crate) struct RxPage {
+    dev: *mut bindings::device,
+    page: *mut bindings::page,
+    order: u32,
+    pub(crate) dma_handle: u64,
+    len: usize,
+}
+
+impl RxPage {
+    /// Allocate and map a buffer of at least `len` bytes. It's called from the napi poll too, so the allocation
+    /// doesn't sleep.
+    pub(crate) fn try_new(dev: &dyn RawDevice, len: usize) -> Result<Self> {
+        let dev = dev.raw_device();
+        let order = (len.max(kernel::PAGE_SIZE) / kernel::PAGE_SIZE).next_power_of_two().trailing_zeros();
+
+        // SAFETY: FFI call.
+        let page = unsafe{helpers::r4l_e1000_dev_alloc_pages(order)};
+        if page.is_null() {
+            return Err(kernel::error::code::ENOMEM);
+        }
+
+        // SAFETY: `dev` is valid, and the page is owned by us until it's freed in `drop()` or given to a skb.
+        let dma_handle = unsafe{bindings::dma_map_page_attrs(dev, page, 0, len, bindings::dma_data_direction_DMA_FROM_DEVICE, 0)};
+        // SAFETY: `dev` is valid.
+        if unsafe{helpers::r4l_e1000_dma_mapping_error(dev, dma_handle)} != 0 {
+            // SAFETY: the page is allocated above and not used by anyone else.
+            unsafe{bindings::__free_pages(page, order)};
+            return Err(kernel::error::code::ENOMEM);
+        }
+        Ok(Self{dev, page, order, dma_handle, len})
+    }
+
+    /// Unmap the buffer and append its first `len` bytes to `skb` as a new page frag, corresponding to the C version
+    /// `skb_fill_page_desc()` in `e1000_clean_jumbo_rx_irq()`. The skb owns the page from now on. The caller must make
+    /// sure `skb` has room for another frag.
+    pub(crate) fn add_to_skb(self, skb: &SkBuff, len: usize) {
+        let this = core::mem::ManuallyDrop::new(self);
+        let nr_frags = skb.nr_frags();
+        // SAFETY: the mapping is created in `try_new()`, and the page reference is given to the skb, which is owned by
+        // the rx path.
+        unsafe{
+            bindings::dma_unmap_page_attrs(this.dev, this.dma_handle, this.len, bindings::dma_data_direction_DMA_FROM_DEVICE, 0);
+            bindings::skb_add_rx_frag(as_raw(skb), nr_frags as i32, this.page, 0, len as i32, (kernel::PAGE_SIZE << this.order) as u32);
+        }
+    }
+}
+
+impl Drop for RxPage {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is created in `try_new()` with the same device, size and direction, and the page is
+        // still ours since it hasn't been given to a skb.
+        unsafe{
+            bindings::dma_unmap_page_attrs(self.dev, self.dma_handle, self.len, bindings::dma_data_direction_DMA_FROM_DEVICE, 0);
+            bindings::__free_pages(self.page, self.order);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use core::cell::UnsafeCell;
+
+use kernel::bindings;
+
+use crate::helpers;
+
+/// A thin wrapper of the C `struct work_struct`, run on the system workqueue. The raw C bindings are used here since
+/// the work items of the R4L crate must be owned by an `Arc`, while these are embedded in the netdev private data.
+#[repr(transparent)]
+pub(crate) struct Work(UnsafeCell<bindings::work_struct>);
+
+impl Work {
+    pub(crate) fn new() -> Self {
+        // SAFETY: `work_struct` is a plain C struct, all zeros is a valid (but not initialized) value for it.
+        Self(UnsafeCell::new(unsafe{core::mem::zeroed()}))
+    }
+
+    /// Set the function of the work item, corresponding to the C macro `INIT_WORK()`. The function gets a pointer to
+    /// the inner `work_struct`, which is also a pointer to this `Work`, and can use `container_of!` to find the owner.
+    ///
+    /// # Safety
+    ///
+    /// The work item mustn't be pending, and it mustn't be moved until `cancel_sync()` is called.
+    pub(crate) unsafe fn init(&self, func: unsafe extern "C" fn(*mut bindings::work_struct)) {
+        // SAFETY: the work item is not pending, as required by the caller.
+        unsafe{helpers::r4l_e1000_init_work(self.0.get(), Some(func))};
+    }
+
+    /// Queue the work item on the system workqueue, the C version `schedule_work()`. Nothing happens if it's already
+    /// pending, then false is returned.
+    pub(crate) fn schedule(&self) -> bool {
+        // SAFETY: the work item is initialized by `init()`, and `system_wq` lives as long as the kernel.
+        unsafe{bindings::queue_work_on(bindings::WORK_CPU_UNBOUND as _, bindings::system_wq, self.0.get())}
+    }
+
+    /// Cancel the work item and wait for the running function to finish. The function can't queue it again meanwhile.
+    /// It's fine to call it on an idle work item, as long as `init()` has been called once.
+    pub(crate) fn cancel_sync(&self) {
+        // SAFETY: the work item is initialized by `init()`.
+        unsafe{bindings::cancel_work_sync(self.0.get())};
+    }
+}
+
+/// A thin wrapper of the C `struct delayed_work`, a work item queued on the system workqueue after a delay.
+#[repr(transparent)]
+pub(crate) struct DelayedWork(UnsafeCell<bindings::delayed_work>);
+
+impl DelayedWork {
+    pub(crate) fn new() -> Self {
+        // SAFETY: `delayed_work` is a plain C struct, all zeros is a valid (but not initialized) value for it.
+        Self(UnsafeCell::new(unsafe{core::mem::zeroed()}))
+    }
+
+    /// Set the function of the work item, corresponding to the C macro `INIT_DELAYED_WORK()`. The function gets a
+    /// pointer to the inner `work_struct`, which is also a pointer to this `DelayedWork`.
+    ///
+    /// # Safety
+    ///
+    /// The work item mustn't be pending, and it mustn't be moved until `cancel_sync()` is called.
+    pub(crate) unsafe fn init(&self, func: unsafe extern "C" fn(*mut bindings::work_struct)) {
+        // SAFETY: the work item is not pending, as required by the caller. The timer queues the work when it fires.
+        unsafe{helpers::r4l_e1000_init_delayed_work(self.0.get(), Some(func))};
+    }
+
+    /// Queue the work item on the system workqueue `msecs` milliseconds later, the C version `schedule_delayed_work()`.
+    /// Nothing happens if it's already pending.
+    pub(crate) fn schedule_msecs(&self, msecs: u32) {
+        // SAFETY: the work item is initialized by `init()`, and `system_wq` lives as long as the kernel.
+        unsafe{
+            bindings::queue_delayed_work_on(bindings::WORK_CPU_UNBOUND as _, bindings::system_wq, self.0.get(), bindings::__msecs_to_jiffies(msecs));
+        }
+    }
+
+    /// Cancel the work item, including a pending timer, and wait for the running function to finish. The function
+    /// can't queue it again meanwhile.
+    pub(crate) fn cancel_sync(&self) {
+        // SAFETY: the work item is initialized by `init()`.
+        unsafe{bindings::cancel_delayed_work_sync(self.0.get())};
+    }
+}